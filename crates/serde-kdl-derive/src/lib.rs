@@ -0,0 +1,408 @@
+//! `#[derive(KdlNode)]`, for types with a fixed KDL node layout.
+//!
+//! See `serde_kdl::node` for the runtime side. Structs with named fields,
+//! unit structs, and enums of such variants are supported; each struct or
+//! variant is one node, named by `#[kdl(name = "...")]` or else its name
+//! in kebab-case.
+//!
+//! Each field is read from and written to the node as decided by its
+//! attribute:
+//!
+//! - `#[kdl(argument)]`: the next argument.
+//! - `#[kdl(arguments)]`: all remaining arguments, into a `Vec`.
+//! - `#[kdl(property)]`: a property, named after the field.
+//! - `#[kdl(children)]`: all remaining child nodes, into a `Vec` of
+//!   `KdlNode`s.
+//! - `#[kdl(type_name)]`: the node's type annotation, as an `Option<String>`.
+//! - no attribute: a child node, named after the field, holding a SiK value.
+//!
+//! Properties and child nodes can be renamed with `#[kdl(name = "...")]`;
+//! by default, their name is the field's with `_` replaced by `-`. Absent
+//! `Option` fields read as `None`, and are not written when `None`, except
+//! for arguments, which are written as `null` to keep their position.
+//!
+//! Arguments are written first, then properties, then SiK children, then
+//! `KdlNode` children, each in field order.
+
+use {
+    proc_macro2::{Span, TokenStream},
+    quote::quote,
+    syn::{
+        ext::IdentExt, parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error,
+        Fields, Ident, LitStr, Result, Type,
+    },
+};
+
+#[proc_macro_derive(KdlNode, attributes(kdl))]
+pub fn derive_kdl_node(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Argument,
+    Arguments,
+    Property,
+    Child,
+    Children,
+    TypeName,
+}
+
+struct Field {
+    ident: Ident,
+    role: Role,
+    name: String,
+    optional: bool,
+}
+
+/// One node: a struct, or an enum variant.
+struct Shape {
+    /// The path to construct and destructure the node with.
+    path: TokenStream,
+    name: String,
+    fields: Option<Vec<Field>>,
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let (shapes, is_enum) = match &input.data {
+        Data::Struct(data) => {
+            let name = node_name(&input.attrs, ident)?;
+            let path = quote!(Self);
+            (vec![shape(path, name, &data.fields)?], false)
+        }
+        Data::Enum(data) => {
+            let shapes = data
+                .variants
+                .iter()
+                .map(|variant| {
+                    let name = node_name(&variant.attrs, &variant.ident)?;
+                    let variant_ident = &variant.ident;
+                    shape(quote!(Self::#variant_ident), name, &variant.fields)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            (shapes, true)
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span,
+                "KdlNode cannot be derived for unions",
+            ))
+        }
+    };
+
+    let node_name_arms = shapes.iter().map(|shape| {
+        let pattern = shape.pattern(&[]);
+        let name = &shape.name;
+        quote!(#pattern => #name,)
+    });
+
+    let type_name_arms = shapes.iter().map(|shape| {
+        let type_name = shape.fields_with(Role::TypeName).next();
+        match type_name {
+            Some(field) => {
+                let pattern = shape.pattern(&[&field.ident]);
+                let ident = &field.ident;
+                quote!(#pattern => ::std::option::Option::as_deref(#ident),)
+            }
+            None => {
+                let pattern = shape.pattern(&[]);
+                quote!(#pattern => ::std::option::Option::None,)
+            }
+        }
+    });
+
+    let encode_arms = shapes.iter().map(Shape::encode);
+
+    let decode = if is_enum {
+        let arms = shapes.iter().map(|shape| {
+            let name = &shape.name;
+            let decode = shape.decode();
+            quote!(#name => { #decode })
+        });
+        let expected = shapes
+            .iter()
+            .map(|shape| format!("`{}`", shape.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let message = format!("unknown node `{{}}`, expected one of {}", expected);
+        quote! {
+            match __decoder.name() {
+                #(#arms)*
+                __name => ::std::result::Result::Err(
+                    __decoder.error(::std::format_args!(#message, __name)),
+                ),
+            }
+        }
+    } else {
+        let shape = &shapes[0];
+        let name = &shape.name;
+        let message = format!("expected node `{}`, found `{{}}`", name);
+        let decode = shape.decode();
+        quote! {
+            if __decoder.name() != #name {
+                return ::std::result::Result::Err(
+                    __decoder.error(::std::format_args!(#message, __decoder.name())),
+                );
+            }
+            #decode
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::serde_kdl::node::KdlNode for #ident #ty_generics #where_clause {
+            fn node_name(&self) -> &'static str {
+                match self {
+                    #(#node_name_arms)*
+                }
+            }
+
+            fn type_name(&self) -> ::std::option::Option<&str> {
+                match self {
+                    #(#type_name_arms)*
+                }
+            }
+
+            fn encode<__F: ::serde_kdl::ser::Format>(
+                &self,
+                __encoder: &mut ::serde_kdl::node::NodeEncoder<'_, '_, __F>,
+            ) -> ::serde_kdl::Result {
+                match self {
+                    #(#encode_arms)*
+                }
+                ::std::result::Result::Ok(())
+            }
+
+            fn decode(
+                __decoder: &mut ::serde_kdl::node::NodeDecoder<'_, '_>,
+            ) -> ::serde_kdl::Result<Self> {
+                #decode
+            }
+        }
+    })
+}
+
+impl Shape {
+    fn fields_with(&self, role: Role) -> impl Iterator<Item = &Field> {
+        self.fields
+            .iter()
+            .flatten()
+            .filter(move |field| field.role == role)
+    }
+
+    /// A pattern matching this shape, binding the given fields by reference.
+    fn pattern(&self, bind: &[&Ident]) -> TokenStream {
+        let path = &self.path;
+        match self.fields {
+            Some(_) => quote!(#path { #(#bind,)* .. }),
+            None => quote!(#path),
+        }
+    }
+
+    fn encode(&self) -> TokenStream {
+        let idents = (self.fields.iter().flatten())
+            .filter(|field| field.role != Role::TypeName)
+            .map(|field| &field.ident);
+        let pattern = self.pattern(&idents.collect::<Vec<_>>());
+        let writes = [
+            Role::Argument,
+            Role::Arguments,
+            Role::Property,
+            Role::Child,
+            Role::Children,
+        ]
+        .into_iter()
+        .flat_map(|role| self.fields_with(role))
+        .map(|field| {
+            let ident = &field.ident;
+            let name = &field.name;
+            let write = |value| match field.role {
+                Role::Argument => quote!(__encoder.argument(#value)?;),
+                Role::Arguments => quote!(__encoder.arguments(#value)?;),
+                Role::Property => quote!(__encoder.property(#name, #value)?;),
+                Role::Child => quote!(__encoder.child(#name, #value)?;),
+                Role::Children => quote!(__encoder.nodes(#value)?;),
+                Role::TypeName => unreachable!(),
+            };
+            if field.optional && matches!(field.role, Role::Property | Role::Child) {
+                let write = write(quote!(__value));
+                quote! {
+                    if let ::std::option::Option::Some(__value) = #ident {
+                        #write
+                    }
+                }
+            } else {
+                write(quote!(#ident))
+            }
+        });
+        quote!(#pattern => { #(#writes)* })
+    }
+
+    fn decode(&self) -> TokenStream {
+        let path = &self.path;
+        let fields = match &self.fields {
+            Some(fields) => fields,
+            None => return quote!(::std::result::Result::Ok(#path)),
+        };
+        let reads = [
+            Role::TypeName,
+            Role::Argument,
+            Role::Arguments,
+            Role::Property,
+            Role::Child,
+            Role::Children,
+        ]
+        .into_iter()
+        .flat_map(|role| self.fields_with(role))
+        .map(|field| {
+            let ident = &field.ident;
+            let name = &field.name;
+            let read = match field.role {
+                Role::TypeName => quote! {
+                    ::std::option::Option::map(__decoder.type_name(), ::std::convert::Into::into)
+                },
+                Role::Argument => quote!(__decoder.argument(#name)?),
+                Role::Arguments => quote!(__decoder.arguments()?),
+                Role::Property => quote!(__decoder.property(#name)?),
+                Role::Child => quote!(__decoder.child(#name)?),
+                Role::Children => quote!(__decoder.nodes()?),
+            };
+            quote!(let #ident = #read;)
+        });
+        let idents = fields.iter().map(|field| &field.ident);
+        quote! {
+            #(#reads)*
+            ::std::result::Result::Ok(#path { #(#idents,)* })
+        }
+    }
+}
+
+fn shape(path: TokenStream, name: String, fields: &Fields) -> Result<Shape> {
+    let fields = match fields {
+        Fields::Named(fields) => fields,
+        Fields::Unit => {
+            return Ok(Shape {
+                path,
+                name,
+                fields: None,
+            })
+        }
+        Fields::Unnamed(fields) => {
+            return Err(Error::new(
+                fields.span(),
+                "KdlNode cannot be derived for tuple structs or variants",
+            ))
+        }
+    };
+    let fields = fields
+        .named
+        .iter()
+        .map(|field| {
+            let ident = field.ident.clone().unwrap();
+            let mut role = None;
+            let mut name = None;
+            for attr in field
+                .attrs
+                .iter()
+                .filter(|attr| attr.path().is_ident("kdl"))
+            {
+                attr.parse_nested_meta(|meta| {
+                    let new_role = if meta.path.is_ident("argument") {
+                        Role::Argument
+                    } else if meta.path.is_ident("arguments") {
+                        Role::Arguments
+                    } else if meta.path.is_ident("property") {
+                        Role::Property
+                    } else if meta.path.is_ident("children") {
+                        Role::Children
+                    } else if meta.path.is_ident("type_name") {
+                        Role::TypeName
+                    } else if meta.path.is_ident("name") {
+                        name = Some(meta.value()?.parse::<LitStr>()?.value());
+                        return Ok(());
+                    } else {
+                        return Err(meta.error("unknown kdl attribute"));
+                    };
+                    if role.replace(new_role).is_some() {
+                        return Err(meta.error("a field can only have one placement"));
+                    }
+                    Ok(())
+                })?;
+            }
+            let role = role.unwrap_or(Role::Child);
+            if name.is_some() && !matches!(role, Role::Property | Role::Child) {
+                return Err(Error::new(
+                    ident.span(),
+                    "only properties and child nodes can be renamed",
+                ));
+            }
+            Ok(Field {
+                name: name.unwrap_or_else(|| ident.unraw().to_string().replace('_', "-")),
+                optional: is_option(&field.ty),
+                role,
+                ident,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if fields.iter().filter(|f| f.role == Role::TypeName).count() > 1 {
+        return Err(Error::new(
+            Span::call_site(),
+            "only one field can be the type name",
+        ));
+    }
+    Ok(Shape {
+        path,
+        name,
+        fields: Some(fields),
+    })
+}
+
+/// The node name given by `#[kdl(name = "...")]`, or else the kebab-case
+/// form of the type or variant name.
+fn node_name(attrs: &[Attribute], ident: &Ident) -> Result<String> {
+    let mut name = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("kdl")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("unknown kdl attribute"))
+            }
+        })?;
+    }
+    Ok(name.unwrap_or_else(|| kebab_case(&ident.unraw().to_string())))
+}
+
+fn kebab_case(ident: &str) -> String {
+    let mut name = String::new();
+    for (i, c) in ident.char_indices() {
+        if c.is_uppercase() {
+            if i != 0 {
+                name.push('-');
+            }
+            name.extend(c.to_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// Whether the type is spelled as an `Option`. Like serde's
+/// `skip_serializing_if`, this can only go by the type's name.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(ty) => ty
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
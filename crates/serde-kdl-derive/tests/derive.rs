@@ -0,0 +1,123 @@
+use {
+    serde_kdl::node::{from_str, to_string},
+    serde_kdl_derive::KdlNode,
+};
+
+#[derive(KdlNode, Debug, PartialEq)]
+#[kdl(name = "document")]
+struct Schema {
+    #[kdl(children)]
+    nodes: Vec<NodeRule>,
+}
+
+#[derive(KdlNode, Debug, PartialEq)]
+#[kdl(name = "node")]
+struct NodeRule {
+    #[kdl(argument)]
+    name: String,
+    #[kdl(property)]
+    description: Option<String>,
+    #[kdl(property, name = "id")]
+    rule_id: Option<u32>,
+    #[kdl(children)]
+    rules: Vec<Rule>,
+}
+
+#[derive(KdlNode, Debug, PartialEq)]
+enum Rule {
+    Min {
+        #[kdl(argument)]
+        value: u32,
+    },
+    Enum {
+        #[kdl(arguments)]
+        values: Vec<String>,
+    },
+    Type {
+        #[kdl(type_name)]
+        ty: Option<String>,
+        #[kdl(argument)]
+        name: String,
+    },
+    Tags {
+        names: Vec<String>,
+    },
+    Required,
+}
+
+fn schema() -> Schema {
+    Schema {
+        nodes: vec![
+            NodeRule {
+                name: "package".into(),
+                description: Some("A package".into()),
+                rule_id: None,
+                rules: vec![
+                    Rule::Required,
+                    Rule::Min { value: 1 },
+                    Rule::Tags {
+                        names: vec!["root".into()],
+                    },
+                ],
+            },
+            NodeRule {
+                name: "license".into(),
+                description: None,
+                rule_id: Some(2),
+                rules: vec![
+                    Rule::Enum {
+                        values: vec!["MIT".into(), "Apache-2.0".into()],
+                    },
+                    Rule::Type {
+                        ty: Some("spdx".into()),
+                        name: "string".into(),
+                    },
+                ],
+            },
+        ],
+    }
+}
+
+#[test]
+fn roundtrip() -> serde_kdl::Result {
+    let kdl = to_string(&schema())?;
+    insta::assert_snapshot!("schema", kdl);
+    assert_eq!(from_str::<Schema>(&kdl)?, schema());
+    Ok(())
+}
+
+#[test]
+fn handwritten() -> serde_kdl::Result {
+    let kdl = r#"
+        document {
+            node "package" description="A package" {
+                required
+                min 1
+                tags {
+                    names "root"
+                }
+            }
+            node "license" id=2 {
+                enum "MIT" "Apache-2.0"
+                (spdx)type "string"
+            }
+        }
+    "#;
+    assert_eq!(from_str::<Schema>(kdl)?, schema());
+    Ok(())
+}
+
+#[test]
+fn errors() {
+    let error = |kdl| from_str::<Schema>(kdl).unwrap_err().to_string();
+    insta::assert_snapshot!(
+        "unknown node",
+        error("document {\n    node \"a\" {\n        max 1\n    }\n}")
+    );
+    insta::assert_snapshot!("missing argument", error("document {\n    node\n}"));
+    insta::assert_snapshot!(
+        "unexpected property",
+        error("document {\n    node \"a\" colour=\"red\"\n}")
+    );
+    insta::assert_snapshot!("wrong root", error("schema"));
+}
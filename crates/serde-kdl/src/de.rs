@@ -1 +1,2698 @@
+#[cfg(feature = "arbitrary_precision")]
+use crate::number;
+use {
+    crate::{
+        document::{self, Document},
+        error::{self, Limit, LineStarts, Location, Warning},
+        place,
+        read::{self, Event, Limits, Literal, Number, Reader, Span},
+        spanned,
+        tree::{self, Entry, Node},
+        Error, Result,
+    },
+    paste::paste,
+    serde::de::{self, value::StrDeserializer, Error as _, *},
+    std::{
+        borrow::Cow,
+        cell::{Cell, OnceCell, RefCell},
+        collections::HashMap,
+        fmt, io,
+        marker::PhantomData,
+    },
+};
 
+/// Options controlling which SiK extensions [`Deserializer`] accepts.
+///
+/// [`Options::strict`] accepts only base SiK, and [`Options::lenient`] every
+/// standard extension which doesn't change how base SiK reads. The default
+/// is between the two, and the opt-in modes below it are off in all three.
+///
+/// ```
+/// # use serde_kdl::de::Options;
+/// let opt = Options::strict().alternative_map_entries(true);
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Options {
+    /// Accept documents whose root literal node is omitted.
+    pub implied_root: bool,
+    /// Accept the alternative wrapper encodings: `option` as an `enum` of
+    /// `(None)` and `(Some)`, and `newtype_*` and `unit_*` as `tuple_*`.
+    pub alternative_wrappers: bool,
+    /// Accept elements of a `seq` named something other than `-`.
+    pub literal_node_names: bool,
+    /// Read the name of a `seq` element without a type annotation as its
+    /// `enum` variant, e.g. `Circle 1.5` rather than `- (Circle)1.5`.
+    pub variant_node_names: bool,
+    /// Accept arguments after properties in a struct node, reading them as
+    /// if all arguments came first, with a warning.
+    pub mixed_arguments_and_properties: bool,
+    /// Accept type annotations the spec doesn't require, as long as they
+    /// name the type being read: the name of a primitive number or `bool`,
+    /// or of a `unit_struct`, `newtype_struct`, `tuple_struct`, or `struct`.
+    /// Annotations on types without such a name, like strings, are ignored
+    /// with a warning. A byte array's `(base64)` annotation is always
+    /// accepted.
+    pub optional_type_annotations: bool,
+    /// Accept map entries written as a literal node with `key` and `value`
+    /// fields, or with the key and value as its two elements, as well as
+    /// nodes named by their string key.
+    pub alternative_map_entries: bool,
+    /// Accept maps whose entries aren't all written in the same style, with
+    /// a warning for each entry which differs from the first.
+    pub mixed_map_entries: bool,
+    /// Where a `newtype_*` could be read either transparently or as
+    /// a `tuple_*` of the wrapped value, prefer the `tuple_*` reading.
+    pub newtype_as_tuple: bool,
+    /// Read all of a struct's child nodes sharing a name as one field. If the
+    /// field is a `seq`, each node is one element; otherwise, there must be
+    /// exactly one node.
+    pub repeated_seq_fields: bool,
+    /// Read properties and child nodes which are not fields of a struct as
+    /// the fields of its last field, a `struct` flattened into its node.
+    pub flatten_tail_struct: bool,
+    /// Read all of a struct node's arguments as its first field, rather than
+    /// as one field each. If that field is not a `seq`, there must be exactly
+    /// one argument.
+    pub leading_seq_as_arguments: bool,
+    /// Read a node's type annotation as a field with this name, where the
+    /// node is read as a map, or as a struct with a field of this name, e.g.
+    /// the tag of an internally or adjacently tagged enum.
+    pub tag_as_type_annotation: Option<&'static str>,
+    /// Caps on the size of the document, for reading untrusted input.
+    pub limits: Limits,
+    /// Read values of one type as another where a hand-written document
+    /// likely means it, e.g. `port "8080"` as a number.
+    pub coercion: Option<Coercion>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            implied_root: true,
+            alternative_wrappers: true,
+            literal_node_names: true,
+            variant_node_names: false,
+            mixed_arguments_and_properties: false,
+            optional_type_annotations: true,
+            alternative_map_entries: true,
+            mixed_map_entries: true,
+            newtype_as_tuple: false,
+            repeated_seq_fields: false,
+            flatten_tail_struct: false,
+            leading_seq_as_arguments: false,
+            tag_as_type_annotation: None,
+            limits: Limits::default(),
+            coercion: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept only base SiK, for validating documents.
+    ///
+    /// The root node may still be implied, as that is how the serializer
+    /// writes a `struct`, `map`, or `seq` document, so this reads anything
+    /// the serializer writes with its default options, except for maps with
+    /// keys that aren't strings. Those are written as alternative map entries,
+    /// which [`Options::alternative_map_entries`] accepts. A string key of `-`,
+    /// which can't name its node, is accepted with `key` and `value` fields.
+    pub fn strict() -> Self {
+        Self {
+            implied_root: true,
+            alternative_wrappers: false,
+            literal_node_names: false,
+            variant_node_names: false,
+            mixed_arguments_and_properties: false,
+            optional_type_annotations: false,
+            alternative_map_entries: false,
+            mixed_map_entries: false,
+            ..Self::default()
+        }
+    }
+
+    /// Accept every standard extension, for loading handwritten documents.
+    pub fn lenient() -> Self {
+        Self {
+            implied_root: true,
+            alternative_wrappers: true,
+            literal_node_names: true,
+            variant_node_names: true,
+            mixed_arguments_and_properties: true,
+            optional_type_annotations: true,
+            alternative_map_entries: true,
+            mixed_map_entries: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn implied_root(mut self, yes: bool) -> Self {
+        self.implied_root = yes;
+        self
+    }
+
+    pub fn alternative_wrappers(mut self, yes: bool) -> Self {
+        self.alternative_wrappers = yes;
+        self
+    }
+
+    pub fn literal_node_names(mut self, yes: bool) -> Self {
+        self.literal_node_names = yes;
+        self
+    }
+
+    pub fn variant_node_names(mut self, yes: bool) -> Self {
+        self.variant_node_names = yes;
+        self
+    }
+
+    pub fn mixed_arguments_and_properties(mut self, yes: bool) -> Self {
+        self.mixed_arguments_and_properties = yes;
+        self
+    }
+
+    pub fn optional_type_annotations(mut self, yes: bool) -> Self {
+        self.optional_type_annotations = yes;
+        self
+    }
+
+    pub fn alternative_map_entries(mut self, yes: bool) -> Self {
+        self.alternative_map_entries = yes;
+        self
+    }
+
+    pub fn mixed_map_entries(mut self, yes: bool) -> Self {
+        self.mixed_map_entries = yes;
+        self
+    }
+
+    pub fn newtype_as_tuple(mut self, yes: bool) -> Self {
+        self.newtype_as_tuple = yes;
+        self
+    }
+
+    pub fn repeated_seq_fields(mut self, yes: bool) -> Self {
+        self.repeated_seq_fields = yes;
+        self
+    }
+
+    pub fn flatten_tail_struct(mut self, yes: bool) -> Self {
+        self.flatten_tail_struct = yes;
+        self
+    }
+
+    pub fn leading_seq_as_arguments(mut self, yes: bool) -> Self {
+        self.leading_seq_as_arguments = yes;
+        self
+    }
+
+    pub fn tag_as_type_annotation(mut self, tag: Option<&'static str>) -> Self {
+        self.tag_as_type_annotation = tag;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn coercion(mut self, coercion: Option<Coercion>) -> Self {
+        self.coercion = coercion;
+        self
+    }
+}
+
+/// Which values [`Options::coercion`] reads as another type.
+///
+/// Only a value read as a string, number, or `bool` is coerced; one read
+/// through `deserialize_any` is always read as written.
+///
+/// ```
+/// # use serde_kdl::de::{Coercion, Options};
+/// let opt = Options::new().coercion(Some(Coercion::new().warn(true)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Coercion {
+    /// Read a number as a string of its text as written, e.g. `version 1.0`
+    /// as `"1.0"`.
+    pub numbers_as_strings: bool,
+    /// Read a string which is a valid KDL number as that number.
+    pub strings_as_numbers: bool,
+    /// Strings read as `true`, ignoring ASCII case.
+    pub true_strings: Vec<Cow<'static, str>>,
+    /// Strings read as `false`, ignoring ASCII case.
+    pub false_strings: Vec<Cow<'static, str>>,
+    /// Add a warning for each value coerced.
+    pub warn: bool,
+}
+
+impl Default for Coercion {
+    fn default() -> Self {
+        Self {
+            numbers_as_strings: true,
+            strings_as_numbers: true,
+            true_strings: vec!["true".into(), "yes".into(), "on".into()],
+            false_strings: vec!["false".into(), "no".into(), "off".into()],
+            warn: false,
+        }
+    }
+}
+
+impl Coercion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn numbers_as_strings(mut self, yes: bool) -> Self {
+        self.numbers_as_strings = yes;
+        self
+    }
+
+    pub fn strings_as_numbers(mut self, yes: bool) -> Self {
+        self.strings_as_numbers = yes;
+        self
+    }
+
+    pub fn true_strings<S: Into<Cow<'static, str>>>(
+        mut self,
+        strings: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.true_strings = strings.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn false_strings<S: Into<Cow<'static, str>>>(
+        mut self,
+        strings: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.false_strings = strings.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn warn(mut self, yes: bool) -> Self {
+        self.warn = yes;
+        self
+    }
+}
+
+/// Serde [`Deserializer`](serde::Deserializer) for KDL documents.
+///
+/// The document is parsed in full before deserialization starts, so any
+/// syntax error is reported before the target type sees any data.
+///
+/// Problems which don't stop deserialization, such as nodes commented out
+/// with `/-`, are collected as [`Warning`]s; see [`take_warnings`].
+///
+/// [`take_warnings`]: Deserializer::take_warnings
+#[derive(Debug)]
+pub struct Deserializer<'de> {
+    src: &'de str,
+    /// Where `src` starts in the input, when it is one node of a stream.
+    base: Location,
+    opt: Options,
+    warnings: RefCell<Vec<Warning>>,
+    /// Found on the first error or warning, to find its line and column.
+    lines: OnceCell<LineStarts>,
+}
+
+impl<'de> Deserializer<'de> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(src: &'de str) -> Self {
+        Self::from_str_with_options(src, Options::default())
+    }
+
+    pub fn from_str_with_options(src: &'de str, opt: Options) -> Self {
+        Deserializer {
+            src,
+            base: Location::start(),
+            opt,
+            warnings: RefCell::new(Vec::new()),
+            lines: OnceCell::new(),
+        }
+    }
+
+    /// The warnings collected so far, in the order they were found.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        self.warnings.take()
+    }
+
+    pub(crate) fn location(&self, offset: usize) -> Location {
+        let lines = self.lines.get_or_init(|| LineStarts::new(self.src));
+        Location::in_lines(self.src, lines, offset).relative_to(self.base)
+    }
+
+    /// Read `value` as a [`Spanned`](crate::Spanned) with the given span.
+    fn spanned<V, D>(&self, span: Span, value: D, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        D: serde::Deserializer<'de, Error = Error>,
+    {
+        visitor.visit_map(SpannedAccess {
+            cx: self,
+            span,
+            field: 0,
+            value: Some(value),
+        })
+    }
+
+    fn warn(&self, message: impl std::fmt::Display, offset: usize) {
+        self.warnings.borrow_mut().push(Warning {
+            message: message.to_string(),
+            location: self.location(offset),
+        });
+    }
+
+    /// Check a type annotation which the spec doesn't require. With
+    /// [`Options::optional_type_annotations`], it must be `expected`.
+    fn check_type(&self, ty: Option<&str>, expected: Option<&str>, offset: usize) -> Result {
+        match (ty, expected) {
+            (None, _) => Ok(()),
+            (Some(ty), Some(expected)) if self.opt.optional_type_annotations => {
+                if ty == expected {
+                    Ok(())
+                } else {
+                    Err(Error::data(
+                        format_args!("type annotation `({})` does not match `{}`", ty, expected),
+                        self.location(offset),
+                    ))
+                }
+            }
+            (Some(ty), None) if self.opt.optional_type_annotations => {
+                self.warn(format_args!("ignored type annotation `({})`", ty), offset);
+                Ok(())
+            }
+            (Some(ty), _) => Err(Error::data(
+                format_args!("unexpected type annotation `({})`", ty),
+                self.location(offset),
+            )),
+        }
+    }
+}
+
+macro_rules! forward_to_root {
+    ($($method:ident($($arg:ident: $T:ty),*);)*) => {$(
+        fn $method<V: Visitor<'de>>(self, $($arg: $T,)* visitor: V) -> Result<V::Value> {
+            let (root, skipped) = tree::parse_with_skipped_nodes(self.src, self.opt.limits)?;
+            for span in skipped {
+                self.warn("ignored node commented out with `/-`", span.start);
+            }
+            Root { cx: self, node: &root }.$method($($arg,)* visitor)
+        }
+    )*};
+}
+
+impl<'de> serde::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    forward_to_root! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_option();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_newtype_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+}
+
+fn unexpected<'a>(value: &'a Literal<'_>) -> Unexpected<'a> {
+    match value {
+        Literal::String(s) => Unexpected::Str(s),
+        Literal::Number(_) => Unexpected::Other("number"),
+        Literal::Bool(b) => Unexpected::Bool(*b),
+        Literal::Null => Unexpected::Unit,
+    }
+}
+
+fn unexpected_node(node: &Node<'_>) -> Unexpected<'static> {
+    if node.entries.iter().any(|entry| entry.name.is_some()) {
+        Unexpected::Other("node with properties")
+    } else if !node.children().is_empty() {
+        Unexpected::Other("node with children")
+    } else if node.entries.is_empty() {
+        Unexpected::Other("node without values")
+    } else {
+        Unexpected::Other("node with multiple values")
+    }
+}
+
+/// Deserializes the document root.
+///
+/// Compound values may either be the children of a single root literal
+/// node, or (with [`Options::implied_root`]) the top-level nodes themselves.
+/// A document consisting of a single untyped literal node is always read
+/// as the former, as is a typed one with [`Options::tag_as_type_annotation`].
+struct Root<'a, 'de> {
+    cx: &'a Deserializer<'de>,
+    node: &'a Node<'de>,
+}
+
+macro_rules! forward_to_single {
+    ($($method:ident($($arg:ident: $T:ty),*);)*) => {$(
+        fn $method<V: Visitor<'de>>(self, $($arg: $T,)* visitor: V) -> Result<V::Value> {
+            self.single()?.$method($($arg,)* visitor)
+        }
+    )*};
+}
+
+macro_rules! forward_to_compound {
+    ($($method:ident($($arg:ident: $T:ty),*);)*) => {$(
+        fn $method<V: Visitor<'de>>(self, $($arg: $T,)* visitor: V) -> Result<V::Value> {
+            self.compound()?.$method($($arg,)* visitor)
+        }
+    )*};
+}
+
+impl<'a, 'de> Root<'a, 'de> {
+    fn single(&self) -> Result<NodeDeserializer<'a, 'de>> {
+        match self.node.children() {
+            [node] => NodeDeserializer::element(self.cx, node),
+            [] => Err(Error::data(
+                "expected a root node, found empty document",
+                self.cx.location(self.node.span.end),
+            )),
+            [_, node, ..] => Err(Error::data(
+                "expected a single root node",
+                self.cx.location(node.span.start),
+            )),
+        }
+    }
+
+    fn literal_root(&self) -> Option<&'a Node<'de>> {
+        match self.node.children() {
+            [node] if node.is_literal() && node.ty.is_none() => Some(node),
+            [node] if node.is_literal() && self.cx.opt.tag_as_type_annotation.is_some() => {
+                Some(node)
+            }
+            _ => None,
+        }
+    }
+
+    fn compound(&self) -> Result<NodeDeserializer<'a, 'de>> {
+        if !self.cx.opt.implied_root {
+            return self.single();
+        }
+        Ok(NodeDeserializer::new(
+            self.cx,
+            self.literal_root().unwrap_or(self.node),
+        ))
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for Root<'_, 'de> {
+    type Error = Error;
+
+    forward_to_single! {
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+    }
+
+    forward_to_compound! {
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == spanned::NAME {
+            let span = self.literal_root().map_or(self.node.span, |node| node.span);
+            return self.cx.spanned(span, self, visitor);
+        }
+        self.compound()?.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.node.children() {
+            // a typed one is an enum, rather than the only element of a seq
+            [node] if node.is_literal() => {
+                NodeDeserializer::new(self.cx, node).deserialize_any(visitor)
+            }
+            _ => self.compound()?.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let wrappers = self.cx.opt.alternative_wrappers;
+        let root = match self.node.children() {
+            // a `(Some)` one keeps its value apart from `None`, as for `Some(None)`
+            [node] if wrappers && node.is_literal() && node.ty.as_deref() == Some("Some") => {
+                Some(node)
+            }
+            _ => self.literal_root(),
+        };
+        match root {
+            Some(node) => NodeDeserializer::new(self.cx, node).deserialize_option(visitor),
+            None => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == document::NAME {
+            return self.compound()?.deserialize_newtype_struct(name, visitor);
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+/// Deserializes a KDL node as a whole.
+pub(crate) struct NodeDeserializer<'a, 'de> {
+    cx: &'a Deserializer<'de>,
+    node: &'a Node<'de>,
+    /// The node's type annotation, until consumed as an enum variant.
+    ty: Option<&'a str>,
+    /// With [`Options::variant_node_names`], the name of a `seq` element,
+    /// read as its variant if it has no type annotation.
+    named_variant: Option<&'a str>,
+}
+
+macro_rules! forward_to_leaf {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            let at = self.at();
+            self.leaf(&visitor)?.$method(visitor).map_err(at)
+        }
+    )*};
+}
+
+impl<'a, 'de> NodeDeserializer<'a, 'de> {
+    pub(crate) fn new(cx: &'a Deserializer<'de>, node: &'a Node<'de>) -> Self {
+        NodeDeserializer {
+            cx,
+            node,
+            ty: node.ty.as_deref(),
+            named_variant: None,
+        }
+    }
+
+    /// A node read as an element of a `seq`, which is a literal node.
+    fn element(cx: &'a Deserializer<'de>, node: &'a Node<'de>) -> Result<Self> {
+        let named = !node.is_literal();
+        if named && !cx.opt.literal_node_names && !cx.opt.variant_node_names {
+            return Err(Error::data(
+                format_args!("expected a literal node `-`, found `{}`", node.name),
+                cx.location(node.span.start),
+            ));
+        }
+        Ok(NodeDeserializer {
+            named_variant: (named && cx.opt.variant_node_names).then_some(&*node.name),
+            ..NodeDeserializer::new(cx, node)
+        })
+    }
+
+    fn location(&self) -> Location {
+        self.cx.location(self.node.span.start)
+    }
+
+    /// Attach the node's location to an error without one, only once there
+    /// is an error.
+    fn at(&self) -> impl Fn(Error) -> Error + Copy + 'a {
+        let (cx, offset) = (self.cx, self.node.span.start);
+        move |e| e.at(cx.location(offset))
+    }
+
+    /// The node's single value, for nodes holding a leaf value. The node's
+    /// type annotation, if any, is read as the value's.
+    ///
+    /// With [`Options::alternative_wrappers`], a node whose only content is
+    /// a single literal child node is also accepted, as that is how a
+    /// `tuple_*` wrapper of a leaf value looks.
+    fn leaf(&self, exp: &dyn Expected) -> Result<ValueDeserializer<'a, 'de>> {
+        match (&self.node.entries[..], self.node.children()) {
+            ([entry], []) if entry.name.is_none() => {
+                let mut value = ValueDeserializer::new(self.cx, entry);
+                if self.ty.is_some() {
+                    if value.ty.is_some() {
+                        self.check_type(None)?;
+                    }
+                    value.ty = self.ty;
+                }
+                Ok(value)
+            }
+            ([], [child]) if self.cx.opt.alternative_wrappers && child.is_literal() => {
+                self.check_type(None)?;
+                NodeDeserializer::new(self.cx, child).leaf(exp)
+            }
+            _ => Err(Error::invalid_type(unexpected_node(self.node), exp).at(self.location())),
+        }
+    }
+
+    fn check_type(&self, expected: Option<&str>) -> Result {
+        self.cx.check_type(self.ty, expected, self.node.span.start)
+    }
+
+    /// Whether the node has no values and no children block, which is how
+    /// an absent value looks (as opposed to an empty collection, `node {}`).
+    fn is_bare(&self) -> bool {
+        self.node.entries.is_empty() && self.node.children.is_none()
+    }
+
+    /// With [`Options::flatten_tail_struct`], split the properties and
+    /// children which aren't the struct's own fields into a node for its
+    /// last field, returning `(own, tail)`, or `None` if there are none.
+    fn split_tail(
+        &self,
+        name: &str,
+        fields: &[&str],
+        tail: &'static str,
+    ) -> Result<Option<(Node<'de>, Node<'de>)>> {
+        let is_own = |key: &Option<Cow<'_, str>>| match key {
+            Some(key) => fields.contains(&&**key) || key == tail,
+            None => true,
+        };
+        let (entries, tail_entries): (Vec<_>, Vec<_>) =
+            (self.node.entries.iter().cloned()).partition(|entry| is_own(&entry.name));
+        let (children, tail_children): (Vec<_>, Vec<_>) = (self.node.children().iter().cloned())
+            .partition(|child| is_own(&Some(child.name.clone())));
+        let stray = match (tail_entries.first(), tail_children.first()) {
+            (Some(entry), _) => entry.span.start,
+            (None, Some(child)) => child.span.start,
+            (None, None) => return Ok(None),
+        };
+
+        // The struct's own fields could also be fields of the tail struct,
+        // so anything that doesn't read as exactly one field is ambiguous.
+        let ambiguous = |message: std::fmt::Arguments<'_>, offset| {
+            Err(Error::data(
+                format_args!(
+                    "ambiguous flattened field `{}` of `{}`: {}",
+                    tail, name, message
+                ),
+                self.cx.location(offset),
+            ))
+        };
+        let arguments = entries.iter().filter(|entry| entry.name.is_none());
+        let varargs = self.cx.opt.leading_seq_as_arguments;
+        if let Some(argument) = arguments.clone().nth(fields.len()).filter(|_| !varargs) {
+            return ambiguous(
+                format_args!("it is also given as an argument"),
+                argument.span.start,
+            );
+        }
+        let properties = entries
+            .iter()
+            .filter_map(|entry| Some((entry.name.as_deref()?, entry)));
+        let mut seen = Vec::new();
+        for (key, offset, is_child) in
+            (properties.map(|(key, entry)| (key, entry.span.start, false))).chain(
+                children
+                    .iter()
+                    .map(|child| (&*child.name, child.span.start, true)),
+            )
+        {
+            if key == tail {
+                return ambiguous(format_args!("it is also given directly"), offset);
+            }
+            let repeatable = is_child && self.cx.opt.repeated_seq_fields;
+            if seen.contains(&key) && !repeatable {
+                return ambiguous(
+                    format_args!("field `{}` is given more than once", key),
+                    offset,
+                );
+            }
+            seen.push(key);
+        }
+
+        let own = Node {
+            entries,
+            children: self.node.children.as_ref().map(|_| children),
+            ..self.node.clone()
+        };
+        let tail = Node {
+            ty: None,
+            name: Cow::Borrowed(tail),
+            entries: tail_entries,
+            children: (!tail_children.is_empty()).then_some(tail_children),
+            span: Span {
+                start: stray,
+                end: self.node.span.end,
+            },
+        };
+        Ok(Some((own, tail)))
+    }
+
+    fn is_null(&self) -> bool {
+        self.is_bare()
+            || matches!(
+                &self.node.entries[..],
+                [Entry {
+                    name: None,
+                    ty: None,
+                    value: Literal::Null,
+                    ..
+                }]
+            ) && self.node.children().is_empty()
+    }
+
+    /// The single literal child of a node which is a `tuple_*` of one value.
+    fn tuple_wrapped(&self) -> Option<NodeDeserializer<'a, 'de>> {
+        let opt = &self.cx.opt;
+        match (&self.node.entries[..], self.node.children()) {
+            ([], [child])
+                if opt.alternative_wrappers && opt.newtype_as_tuple && child.is_literal() =>
+            {
+                Some(NodeDeserializer::new(self.cx, child))
+            }
+            _ => None,
+        }
+    }
+
+    /// With [`Options::tag_as_type_annotation`], the tag's name and the
+    /// type annotation to read as its value.
+    fn tag(&self) -> Option<(&'static str, &'a str)> {
+        self.cx.opt.tag_as_type_annotation.zip(self.ty)
+    }
+
+    /// The node with its arguments moved before its properties.
+    fn arguments_first(&self) -> Node<'de> {
+        let (arguments, properties): (Vec<_>, Vec<_>) =
+            (self.node.entries.iter().cloned()).partition(|entry| entry.name.is_none());
+        Node {
+            entries: arguments.into_iter().chain(properties).collect(),
+            ..self.node.clone()
+        }
+    }
+
+    /// Read the node as a map. Its arguments are an error unless they are
+    /// captured by a flattened [`Document`], or, with `leading_arguments`,
+    /// they come first, in which case they are the value of the key `-`.
+    fn map<V: Visitor<'de>>(self, visitor: V, leading_arguments: bool) -> Result<V::Value> {
+        let at = self.at();
+        if self.tag().is_none() {
+            self.check_type(None)?;
+        }
+        let leading = match self.node.entries.iter().position(|e| e.name.is_some()) {
+            _ if !leading_arguments => 0,
+            Some(properties) => properties,
+            None => self.node.entries.len(),
+        };
+        let (arguments, rest) = self.node.entries.split_at(leading);
+        let capture = MapCapture::push();
+        let value = visitor
+            .visit_map(MapAccess {
+                cx: self.cx,
+                node: self.node,
+                identifiers: Cell::new(false),
+                tag: self.tag(),
+                arguments: (!arguments.is_empty()).then_some(arguments),
+                properties: rest.iter(),
+                children: self.node.children().iter(),
+                style: None,
+                value: None,
+            })
+            .map_err(at)?;
+        match rest.iter().find(|entry| entry.name.is_none()) {
+            Some(argument) if !capture.captured() => Err(Error::data(
+                "unexpected argument in map",
+                self.cx.location(argument.span.start),
+            )),
+            _ => Ok(value),
+        }
+    }
+
+    fn check_entry_order(&self) -> Result {
+        let mut seen_property = false;
+        for entry in &self.node.entries {
+            match entry.name {
+                Some(_) => seen_property = true,
+                None if seen_property => {
+                    return Err(Error::data(
+                        "arguments must come before properties",
+                        self.cx.location(entry.span.start),
+                    ))
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for NodeDeserializer<'_, 'de> {
+    type Error = Error;
+
+    forward_to_leaf! {
+        deserialize_bool
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64
+        deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf
+        deserialize_identifier
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let at = self.at();
+        let has_names = self.node.entries.iter().any(|entry| entry.name.is_some())
+            || self.node.children().iter().any(|child| !child.is_literal());
+        match (&self.node.entries[..], self.node.children()) {
+            _ if self.tag().is_some() => self.map(visitor, true),
+            _ if self.ty.is_some() => visitor
+                .visit_map(VariantMapAccess {
+                    variant: self.ty,
+                    value: Some(NodeDeserializer { ty: None, ..self }),
+                })
+                .map_err(at),
+            _ if self.is_bare() => visitor.visit_unit(),
+            ([entry], []) if entry.name.is_none() => ValueDeserializer::new(self.cx, entry)
+                .deserialize_any(visitor)
+                .map_err(at),
+            _ if has_names => self.map(visitor, true),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let at = self.at();
+        match self.ty {
+            Some("None") if self.cx.opt.alternative_wrappers => visitor.visit_none(),
+            Some("Some") if self.cx.opt.alternative_wrappers => match self.tuple_wrapped() {
+                Some(inner) => visitor.visit_some(inner),
+                None => visitor.visit_some(NodeDeserializer { ty: None, ..self }),
+            },
+            _ if self.is_null() => visitor.visit_none(),
+            // the argument may be annotated as the variant instead
+            None => match (&self.node.entries[..], self.node.children()) {
+                ([entry], []) if entry.name.is_none() => {
+                    ValueDeserializer::new(self.cx, entry).deserialize_option(visitor)
+                }
+                _ => visitor.visit_some(self),
+            },
+            _ => visitor.visit_some(self),
+        }
+        .map_err(at)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.is_bare() {
+            self.check_type(None)?;
+            return visitor.visit_unit();
+        }
+        let at = self.at();
+        self.leaf(&visitor)?.deserialize_unit(visitor).map_err(at)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if self.ty == Some(name) && self.cx.opt.optional_type_annotations {
+            self.ty = None;
+        }
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        let at = self.at();
+        if name == document::NAME {
+            self.check_type(None)?;
+            let document = Document::from_tree(self.node);
+            return value::SeqDeserializer::new(document.tokens().into_iter())
+                .deserialize_any(visitor)
+                .map_err(at);
+        }
+        if self.ty == Some(name) && self.cx.opt.optional_type_annotations {
+            self.ty = None;
+        }
+        match self.tuple_wrapped() {
+            Some(inner) if !place::is_wrapper(name) => {
+                self.check_type(None)?;
+                visitor.visit_newtype_struct(inner)
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
+        .map_err(at)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let at = self.at();
+        self.check_type(None)?;
+        if let Some(property) = self.node.entries.iter().find(|entry| entry.name.is_some()) {
+            return Err(Error::data(
+                format_args!(
+                    "unexpected property `{}` in sequence",
+                    property.name.as_deref().unwrap_or_default()
+                ),
+                self.cx.location(property.span.start),
+            ));
+        }
+        let mut seq = SeqAccess {
+            cx: self.cx,
+            arguments: self.node.entries.iter(),
+            children: self.node.children().iter(),
+        };
+        let value = visitor.visit_seq(&mut seq).map_err(at)?;
+        seq.end().map_err(at)?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if self.ty == Some(name) && self.cx.opt.optional_type_annotations {
+            self.ty = None;
+        }
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.map(visitor, false)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == spanned::NAME {
+            return self.cx.spanned(self.node.span, self, visitor);
+        }
+        let at = self.at();
+        let tag = self.tag().filter(|(tag, _)| fields.contains(tag));
+        if tag.is_none() {
+            self.check_type(Some(name))?;
+        }
+        let sorted = match self.check_entry_order() {
+            Err(err) if self.cx.opt.mixed_arguments_and_properties => {
+                let offset = err.location().map_or(0, |location| location.offset);
+                self.cx.warn(
+                    "argument after properties is read as if it came first",
+                    offset,
+                );
+                Some(self.arguments_first())
+            }
+            result => result.map(|()| None)?,
+        };
+        let this = match &sorted {
+            Some(node) => NodeDeserializer { node, ..self },
+            None => self,
+        };
+        let split = match fields.split_last() {
+            Some((tail, fields)) if this.cx.opt.flatten_tail_struct => {
+                this.split_tail(name, fields, tail)?
+            }
+            _ => None,
+        };
+        let (node, tail) = match &split {
+            Some((node, tail)) => (node, Some(tail)),
+            None => (this.node, None),
+        };
+        // arguments come before properties, so are a prefix of the entries
+        let argument_count = node.entries.iter().take_while(|e| e.name.is_none()).count();
+        let (arguments, entries) = match argument_count {
+            1.. if this.cx.opt.leading_seq_as_arguments && !fields.is_empty() => {
+                let (arguments, entries) = node.entries.split_at(argument_count);
+                (Some(arguments), entries)
+            }
+            _ => (None, &node.entries[..]),
+        };
+        visitor
+            .visit_map(StructAccess {
+                cx: this.cx,
+                name,
+                fields,
+                tag,
+                arguments,
+                entries: entries.iter().enumerate(),
+                children: node.children().iter(),
+                repeated: (this.cx.opt.repeated_seq_fields)
+                    .then(|| group_by_name(node.children()).into_iter()),
+                tail,
+                value: None,
+            })
+            .map_err(at)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let at = self.at();
+        let named_variant = self.named_variant.take();
+        if self.ty.is_none() {
+            self.ty = named_variant;
+        }
+        if self.ty.is_none() {
+            if let ([entry], []) = (&self.node.entries[..], self.node.children()) {
+                if entry.name.is_none() {
+                    return ValueDeserializer::new(self.cx, entry)
+                        .deserialize_enum(name, variants, visitor)
+                        .map_err(at);
+                }
+            }
+            return Err(Error::data(
+                format_args!("expected a type annotation naming a variant of `{}`", name),
+                self.location(),
+            ));
+        }
+        visitor.visit_enum(self).map_err(at)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        true
+    }
+}
+
+impl<'a, 'de> EnumAccess<'de> for NodeDeserializer<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(mut self, seed: T) -> Result<(T::Value, Self)> {
+        let variant = self.ty.take().unwrap_or_default();
+        let variant = seed.deserialize(StrDeserializer::<Error>::new(variant))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'a, 'de> VariantAccess<'de> for NodeDeserializer<'a, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result {
+        Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        match self.tuple_wrapped() {
+            Some(inner) => seed.deserialize(inner),
+            None => seed.deserialize(self),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        serde::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        serde::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+/// Deserializes a single KDL argument or property value.
+pub(crate) struct ValueDeserializer<'a, 'de> {
+    cx: &'a Deserializer<'de>,
+    entry: &'a Entry<'de>,
+    /// The value's type annotation, until consumed as an enum variant.
+    ty: Option<&'a str>,
+}
+
+macro_rules! deserialize_integer {
+    ($($T:ident)*) => {paste! {$(
+        fn [<deserialize_ $T>]<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            let at = self.at();
+            self.check_type(Some(stringify!($T)))?;
+            match self.number() {
+                Some(text) => match Number::parse(text) {
+                    Number::Float(v) => Err(Error::invalid_type(Unexpected::Float(v), &visitor)),
+                    number => match number.to_integer() {
+                        Some(v) => visitor.[<visit_ $T>](v),
+                        None => Err(out_of_range(text, stringify!($T))),
+                    },
+                },
+                None => Err(Error::invalid_type(unexpected(&self.entry.value), &visitor)),
+            }
+            .map_err(at)
+        }
+    )*}};
+}
+
+macro_rules! deserialize_float {
+    ($($T:ident)*) => {paste! {$(
+        fn [<deserialize_ $T>]<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            let at = self.at();
+            self.check_type(Some(stringify!($T)))?;
+            match self.number() {
+                Some(text) => match Number::[<parse_ $T>](text) {
+                    Some(v) => visitor.[<visit_ $T>](v),
+                    None => Err(out_of_range(text, stringify!($T))),
+                },
+                None => Err(Error::invalid_type(unexpected(&self.entry.value), &visitor)),
+            }
+            .map_err(at)
+        }
+    )*}};
+}
+
+fn out_of_range(text: &str, ty: &str) -> Error {
+    Error::custom(format_args!("number `{}` is out of range for {}", text, ty))
+}
+
+impl<'a, 'de> ValueDeserializer<'a, 'de> {
+    pub(crate) fn new(cx: &'a Deserializer<'de>, entry: &'a Entry<'de>) -> Self {
+        ValueDeserializer {
+            cx,
+            entry,
+            ty: entry.ty.as_deref(),
+        }
+    }
+
+    fn location(&self) -> Location {
+        self.cx.location(self.entry.span.start)
+    }
+
+    /// Attach the entry's location to an error without one, only once there
+    /// is an error.
+    fn at(&self) -> impl Fn(Error) -> Error + Copy + 'a {
+        let (cx, offset) = (self.cx, self.entry.span.start);
+        move |e| e.at(cx.location(offset))
+    }
+
+    fn check_type(&self, expected: Option<&str>) -> Result {
+        self.cx.check_type(self.ty, expected, self.entry.span.start)
+    }
+
+    fn string(&self, exp: &dyn Expected) -> Result<&'a Cow<'de, str>> {
+        match &self.entry.value {
+            Literal::String(s) => Ok(s),
+            value => Err(Error::invalid_type(unexpected(value), exp)),
+        }
+    }
+
+    /// The text of the number, or with [`Coercion::strings_as_numbers`], of
+    /// the string which is one.
+    fn number(&self) -> Option<&'a str> {
+        match &self.entry.value {
+            Literal::Number(text) => Some(text),
+            Literal::String(s)
+                if self.coerce(|c| c.strings_as_numbers) && read::is_valid_number(s) =>
+            {
+                self.coerced(format_args!("read string `{:?}` as a number", s));
+                Some(s)
+            }
+            _ => None,
+        }
+    }
+
+    /// The `bool`, or with [`Options::coercion`], the string spelling one.
+    fn bool(&self) -> Option<bool> {
+        match &self.entry.value {
+            Literal::Bool(b) => Some(*b),
+            Literal::String(s) => {
+                let coercion = self.cx.opt.coercion.as_ref()?;
+                let spells =
+                    |strings: &[Cow<str>]| strings.iter().any(|t| t.eq_ignore_ascii_case(s));
+                let b = if spells(&coercion.true_strings) {
+                    true
+                } else if spells(&coercion.false_strings) {
+                    false
+                } else {
+                    return None;
+                };
+                self.coerced(format_args!("read string `{:?}` as `{}`", s, b));
+                Some(b)
+            }
+            _ => None,
+        }
+    }
+
+    fn coerce(&self, f: impl FnOnce(&Coercion) -> bool) -> bool {
+        self.cx.opt.coercion.as_ref().is_some_and(f)
+    }
+
+    /// With [`Coercion::warn`], warn that the value was coerced.
+    fn coerced(&self, message: fmt::Arguments) {
+        if self.coerce(|c| c.warn) {
+            self.cx.warn(message, self.entry.span.start);
+        }
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for ValueDeserializer<'_, 'de> {
+    type Error = Error;
+
+    deserialize_integer! {
+        i8 i16 i32 i64 i128
+        u8 u16 u32 u64 u128
+    }
+
+    deserialize_float! {
+        f32 f64
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match &self.entry.value {
+            _ if self.ty.is_some() => visitor
+                .visit_map(VariantMapAccess {
+                    variant: self.ty,
+                    value: Some(ValueDeserializer { ty: None, ..self }),
+                })
+                .map_err(|e| e.at(self.location())),
+            Literal::String(_) => self.deserialize_str(visitor),
+            #[cfg(feature = "arbitrary_precision")]
+            Literal::Number(text) if number::to_exact_json(text).is_some() => {
+                let text = number::to_exact_json(text).unwrap();
+                visitor
+                    .visit_map(de::value::MapDeserializer::new(std::iter::once((
+                        number::TOKEN,
+                        text,
+                    ))))
+                    .map_err(|e: Error| e.at(self.location()))
+            }
+            Literal::Number(text) => match Number::parse(text) {
+                Number::Float(_) => self.deserialize_f64(visitor),
+                number if number.to_integer::<i64>().is_some() => self.deserialize_i64(visitor),
+                number if number.to_integer::<u64>().is_some() => self.deserialize_u64(visitor),
+                Number::Integer { negative: true, .. } => self.deserialize_i128(visitor),
+                Number::Integer { .. } => self.deserialize_u128(visitor),
+                Number::BigInteger(v) => {
+                    let ty = if v < 0.0 { "i128" } else { "u128" };
+                    Err(out_of_range(text, ty).at(self.location()))
+                }
+            },
+            Literal::Bool(b) => visitor.visit_bool(*b),
+            Literal::Null => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.check_type(Some("bool"))?;
+        match self.bool() {
+            Some(b) => visitor.visit_bool(b),
+            None => Err(Error::invalid_type(unexpected(&self.entry.value), &visitor)),
+        }
+        .map_err(|e| e.at(self.location()))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let at = self.at();
+        self.check_type(None)?;
+        let s = self.string(&visitor).map_err(at)?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::invalid_value(Unexpected::Str(s), &visitor)),
+        }
+        .map_err(at)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let at = self.at();
+        self.check_type(None)?;
+        match &self.entry.value {
+            Literal::Number(text) if self.coerce(|c| c.numbers_as_strings) => {
+                self.coerced(format_args!("read number `{}` as a string", text));
+                visitor.visit_borrowed_str(text)
+            }
+            _ => match self.string(&visitor) {
+                Ok(Cow::Borrowed(s)) => visitor.visit_borrowed_str(s),
+                Ok(Cow::Owned(s)) => visitor.visit_str(s),
+                Err(e) => Err(e),
+            },
+        }
+        .map_err(at)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let at = self.at();
+        if self.ty != Some("base64") {
+            self.check_type(None)?;
+        }
+        let s = self.string(&visitor).map_err(at)?;
+        match base64::decode_config(&**s, base64::STANDARD) {
+            Ok(bytes) => visitor.visit_byte_buf(bytes),
+            Err(e) => Err(Error::custom(format_args!("invalid base64: {}", e))),
+        }
+        .map_err(at)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let at = self.at();
+        let wrappers = self.cx.opt.alternative_wrappers;
+        match (self.ty, &self.entry.value) {
+            (Some("None"), _) if wrappers => visitor.visit_none(),
+            (Some("Some"), _) if wrappers => {
+                visitor.visit_some(ValueDeserializer { ty: None, ..self })
+            }
+            (None, Literal::Null) => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+        .map_err(at)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.check_type(None)?;
+        match self.entry.value {
+            Literal::Null => visitor.visit_unit(),
+            ref value => Err(Error::invalid_type(unexpected(value), &visitor)),
+        }
+        .map_err(|e| e.at(self.location()))
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if self.ty == Some(name) && self.cx.opt.optional_type_annotations {
+            self.ty = None;
+        }
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if self.ty == Some(name) && self.cx.opt.optional_type_annotations {
+            self.ty = None;
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        Err(Error::invalid_type(unexpected(&self.entry.value), &visitor).at(self.location()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == spanned::NAME {
+            return self.cx.spanned(self.entry.span, self, visitor);
+        }
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let at = self.at();
+        if self.ty.is_none() {
+            return Err(Error::data(
+                format_args!("expected a type annotation naming a variant of `{}`", name),
+                self.location(),
+            ));
+        }
+        visitor.visit_enum(self).map_err(at)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        true
+    }
+}
+
+impl<'a, 'de> EnumAccess<'de> for ValueDeserializer<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(mut self, seed: T) -> Result<(T::Value, Self)> {
+        let variant = self.ty.take().unwrap_or_default();
+        let variant = seed.deserialize(StrDeserializer::<Error>::new(variant))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'a, 'de> VariantAccess<'de> for ValueDeserializer<'a, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result {
+        Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        serde::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        serde::Deserializer::deserialize_seq(self, visitor)
+    }
+}
+
+/// Reads the start and end of a value's span and then the value itself, as
+/// the fields of a [`Spanned`](crate::Spanned).
+struct SpannedAccess<'a, 'de, D> {
+    cx: &'a Deserializer<'de>,
+    span: Span,
+    field: usize,
+    value: Option<D>,
+}
+
+impl<'de, D> de::MapAccess<'de> for SpannedAccess<'_, 'de, D>
+where
+    D: serde::Deserializer<'de, Error = Error>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match spanned::FIELDS.get(self.field) {
+            Some(&field) => {
+                self.field += 1;
+                seed.deserialize(StrDeserializer::new(field)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let offset = match self.field {
+            1 => self.span.start,
+            2 => self.span.end,
+            _ => {
+                let value = self
+                    .value
+                    .take()
+                    .expect("next_value called before next_key");
+                return seed.deserialize(value);
+            }
+        };
+        let location = self.cx.location(offset);
+        let location = [location.offset, location.line, location.column];
+        seed.deserialize(value::SeqDeserializer::new(location.into_iter()))
+    }
+}
+
+/// Reads a type annotation and the value it is on as a map of one entry, which
+/// is how self-describing formats represent an enum variant.
+struct VariantMapAccess<'a, D> {
+    variant: Option<&'a str>,
+    value: Option<D>,
+}
+
+impl<'de, D> de::MapAccess<'de> for VariantMapAccess<'_, D>
+where
+    D: serde::Deserializer<'de, Error = Error>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.variant.take() {
+            Some(variant) => seed.deserialize(StrDeserializer::new(variant)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .value
+            .take()
+            .expect("next_value called before next_key");
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(usize::from(self.variant.is_some()))
+    }
+}
+
+/// Deserializes a node name or property name, e.g. as a map key.
+///
+/// Like JSON object keys, non-string map keys are parsed from the name.
+struct KeyDeserializer<'a, 'de> {
+    key: &'a Cow<'de, str>,
+    /// What the key is written as, for when it names an unknown field.
+    what: &'static str,
+    /// Set when the key is read as an identifier.
+    identifier: Option<&'a Cell<bool>>,
+}
+
+/// The error of a [`KeyDeserializer`]'s visitor, which keeps an unknown
+/// field apart so that its message can say what the key was written as.
+#[derive(Debug)]
+enum KeyError {
+    UnknownField(String, &'static [&'static str]),
+    Other(Error),
+}
+
+impl KeyError {
+    fn into_error(self, what: &str) -> Error {
+        match self {
+            KeyError::UnknownField(field, expected) => {
+                Error::Custom(error::unknown(what, &field, expected, "fields"))
+            }
+            KeyError::Other(error) => error,
+        }
+    }
+}
+
+impl de::Error for KeyError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        KeyError::Other(Error::custom(message))
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        KeyError::UnknownField(field.into(), expected)
+    }
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::UnknownField(field, _) => write!(f, "unknown field `{}`", field),
+            KeyError::Other(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+macro_rules! deserialize_parsed_key {
+    ($($T:ident)*) => {paste! {$(
+        fn [<deserialize_ $T>]<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            match self.key.parse::<$T>() {
+                Ok(v) => visitor.[<visit_ $T>](v),
+                Err(_) => Err(Error::invalid_value(Unexpected::Str(self.key), &visitor)),
+            }
+        }
+    )*}};
+}
+
+impl<'de> serde::Deserializer<'de> for KeyDeserializer<'_, 'de> {
+    type Error = Error;
+
+    deserialize_parsed_key! {
+        bool
+        i8 i16 i32 i64 i128
+        u8 u16 u32 u64 u128
+        f32 f64
+        char
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let value = match self.key {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str::<KeyError>(s),
+            Cow::Owned(s) => visitor.visit_str(s),
+        };
+        value.map_err(|e| e.into_error(self.what))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        StrDeserializer::<Error>::new(self.key).deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if let Some(identifier) = self.identifier {
+            identifier.set(true);
+        }
+        self.deserialize_any(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct ignored_any
+    }
+}
+
+/// Any single piece of SiK that can hold a Serde value.
+enum Slot<'a, 'de> {
+    Node(&'a Node<'de>),
+    /// A node which is an element of a `seq`.
+    Element(&'a Node<'de>),
+    Value(&'a Entry<'de>),
+    Key(&'a Cow<'de, str>),
+    /// All of the sibling nodes with the same name.
+    Repeated(Vec<&'a Node<'de>>),
+    /// All of a node's arguments, as its leading field.
+    Arguments(&'a [Entry<'de>]),
+    /// A node's type annotation, as its tag field.
+    Tag(&'a str),
+}
+
+impl<'a, 'de> Slot<'a, 'de> {
+    fn deserialize<T: DeserializeSeed<'de>>(
+        self,
+        cx: &'a Deserializer<'de>,
+        seed: T,
+    ) -> Result<T::Value> {
+        match self {
+            Slot::Node(node) => seed.deserialize(NodeDeserializer::new(cx, node)),
+            Slot::Element(node) => seed.deserialize(NodeDeserializer::element(cx, node)?),
+            Slot::Value(entry) => seed.deserialize(ValueDeserializer::new(cx, entry)),
+            Slot::Key(key) => seed.deserialize(KeyDeserializer {
+                key,
+                what: "field",
+                identifier: None,
+            }),
+            Slot::Repeated(nodes) => seed.deserialize(RepeatedDeserializer { cx, nodes }),
+            Slot::Arguments(entries) => seed.deserialize(ArgumentsDeserializer { cx, entries }),
+            Slot::Tag(ty) => seed.deserialize(StrDeserializer::new(ty)),
+        }
+    }
+}
+
+struct SeqAccess<'a, 'de> {
+    cx: &'a Deserializer<'de>,
+    arguments: std::slice::Iter<'a, Entry<'de>>,
+    children: std::slice::Iter<'a, Node<'de>>,
+}
+
+impl<'a, 'de> SeqAccess<'a, 'de> {
+    fn end(&mut self) -> Result {
+        let remaining = self.arguments.len() + self.children.len();
+        if remaining == 0 {
+            Ok(())
+        } else {
+            Err(Error::custom(format_args!(
+                "{} trailing elements in sequence",
+                remaining
+            )))
+        }
+    }
+}
+
+impl<'a, 'de> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if let Some(entry) = self.arguments.next() {
+            Slot::Value(entry).deserialize(self.cx, seed).map(Some)
+        } else if let Some(node) = self.children.next() {
+            Slot::Element(node).deserialize(self.cx, seed).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.arguments.len() + self.children.len())
+    }
+}
+
+/// Reads struct fields from arguments (in field order), properties, and children.
+struct StructAccess<'a, 'de> {
+    cx: &'a Deserializer<'de>,
+    name: &'static str,
+    fields: &'static [&'static str],
+    /// With [`Options::tag_as_type_annotation`], the tag field and the type
+    /// annotation, read before anything else.
+    tag: Option<(&'static str, &'a str)>,
+    entries: std::iter::Enumerate<std::slice::Iter<'a, Entry<'de>>>,
+    children: std::slice::Iter<'a, Node<'de>>,
+    /// With [`Options::repeated_seq_fields`], the children grouped by name,
+    /// read instead of `children`.
+    repeated: Option<std::vec::IntoIter<Vec<&'a Node<'de>>>>,
+    /// With [`Options::leading_seq_as_arguments`], all of the node's
+    /// arguments, read as the first field before anything else.
+    arguments: Option<&'a [Entry<'de>]>,
+    /// The node of a flattened last field, read after everything else.
+    tail: Option<&'a Node<'de>>,
+    value: Option<Slot<'a, 'de>>,
+}
+
+impl<'a, 'de> de::MapAccess<'de> for StructAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if let Some((tag, ty)) = self.tag.take() {
+            self.value = Some(Slot::Tag(ty));
+            return seed.deserialize(StrDeserializer::new(tag)).map(Some);
+        }
+        if let Some(arguments) = self.arguments.take() {
+            self.value = Some(Slot::Arguments(arguments));
+            return seed
+                .deserialize(StrDeserializer::new(self.fields[0]))
+                .map(Some);
+        }
+        if let Some((i, entry)) = self.entries.next() {
+            self.value = Some(Slot::Value(entry));
+            match &entry.name {
+                Some(name) => seed
+                    .deserialize(KeyDeserializer {
+                        key: name,
+                        what: "property",
+                        identifier: None,
+                    })
+                    .map_err(|e| e.at(self.cx.location(entry.span.start)))
+                    .map(Some),
+                None => match self.fields.get(i) {
+                    Some(&field) => seed.deserialize(StrDeserializer::new(field)).map(Some),
+                    None => Err(Error::data(
+                        format_args!(
+                            "too many arguments for `{}`, which has {} fields",
+                            self.name,
+                            self.fields.len()
+                        ),
+                        self.cx.location(entry.span.start),
+                    )),
+                },
+            }
+        } else {
+            let next = match &mut self.repeated {
+                Some(groups) => groups.next().map(|nodes| (nodes[0], Slot::Repeated(nodes))),
+                None => self.children.next().map(|node| (node, Slot::Node(node))),
+            };
+            if let Some((node, value)) = next {
+                self.value = Some(value);
+                return seed
+                    .deserialize(KeyDeserializer {
+                        key: &node.name,
+                        what: "child node",
+                        identifier: None,
+                    })
+                    .map_err(|e| e.at(self.cx.location(node.span.start)))
+                    .map(Some);
+            }
+            if let Some(tail) = self.tail.take() {
+                self.value = Some(Slot::Node(tail));
+                return seed
+                    .deserialize(KeyDeserializer {
+                        key: &tail.name,
+                        what: "child node",
+                        identifier: None,
+                    })
+                    .map_err(|e| e.at(self.cx.location(tail.span.start)))
+                    .map(Some);
+            }
+            Ok(None)
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .value
+            .take()
+            .expect("next_value called before next_key");
+        value.deserialize(self.cx, seed)
+    }
+}
+
+/// Group nodes by name, in the order each name is first seen.
+fn group_by_name<'a, 'de>(nodes: &'a [Node<'de>]) -> Vec<Vec<&'a Node<'de>>> {
+    let mut groups: Vec<Vec<_>> = Vec::new();
+    let mut indices = HashMap::new();
+    for node in nodes {
+        let index = *indices.entry(&*node.name).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[index].push(node);
+    }
+    groups
+}
+
+/// Deserializes all of the same-named sibling nodes for a struct field.
+///
+/// A `seq` has one element per node; anything else must be a single node.
+struct RepeatedDeserializer<'a, 'de> {
+    cx: &'a Deserializer<'de>,
+    /// The nodes, of which there is at least one.
+    nodes: Vec<&'a Node<'de>>,
+}
+
+impl<'a, 'de> RepeatedDeserializer<'a, 'de> {
+    fn single(&self) -> Result<NodeDeserializer<'a, 'de>> {
+        match self.nodes[..] {
+            [node] => Ok(NodeDeserializer::new(self.cx, node)),
+            [first, duplicate, ..] => Err(Error::data(
+                format_args!("duplicate field `{}`", first.name),
+                self.cx.location(duplicate.span.start),
+            )),
+            [] => unreachable!("repeated field without any nodes"),
+        }
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for RepeatedDeserializer<'_, 'de> {
+    type Error = Error;
+
+    forward_to_single! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == spanned::NAME {
+            let (first, last) = (self.nodes[0], self.nodes[self.nodes.len() - 1]);
+            let span = Span {
+                start: first.span.start,
+                end: last.span.end,
+            };
+            return self.cx.spanned(span, self, visitor);
+        }
+        self.single()?.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.single() {
+            Ok(node) if node.is_null() => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(RepeatedAccess {
+            cx: self.cx,
+            nodes: self.nodes.into_iter(),
+        })
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+struct RepeatedAccess<'a, 'de, I> {
+    cx: &'a Deserializer<'de>,
+    nodes: I,
+}
+
+impl<'a, 'de, I> de::SeqAccess<'de> for RepeatedAccess<'a, 'de, I>
+where
+    I: Iterator<Item = &'a Node<'de>>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.nodes.next() {
+            Some(node) => Slot::Node(node).deserialize(self.cx, seed).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Deserializes all of a node's arguments as its leading struct field.
+///
+/// A `seq` has one element per argument; anything else must be a single
+/// argument.
+struct ArgumentsDeserializer<'a, 'de> {
+    cx: &'a Deserializer<'de>,
+    entries: &'a [Entry<'de>],
+}
+
+impl<'a, 'de> ArgumentsDeserializer<'a, 'de> {
+    fn single(&self) -> Result<ValueDeserializer<'a, 'de>> {
+        match self.entries {
+            [entry] => Ok(ValueDeserializer::new(self.cx, entry)),
+            _ => Err(Error::data(
+                "expected a single argument",
+                self.cx.location(self.entries[1].span.start),
+            )),
+        }
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for ArgumentsDeserializer<'_, 'de> {
+    type Error = Error;
+
+    forward_to_single! {
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == spanned::NAME {
+            let span = Span {
+                start: self.entries[0].span.start,
+                end: self.entries[self.entries.len() - 1].span.end,
+            };
+            return self.cx.spanned(span, self, visitor);
+        }
+        self.single()?.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.entries {
+            [_] => self.single()?.deserialize_any(visitor),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.entries {
+            [entry] if entry.value == Literal::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(SeqAccess {
+            cx: self.cx,
+            arguments: self.entries.iter(),
+            children: [].iter(),
+        })
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+/// Reads map entries from properties and children.
+///
+/// Each child node is one of the map entry encodings:
+/// - a named node, where the name is the key;
+/// - a literal node with a `key` and `value` field; or
+/// - a literal node with two elements, the key and value.
+struct MapAccess<'a, 'de> {
+    cx: &'a Deserializer<'de>,
+    node: &'a Node<'de>,
+    /// Whether the keys have been read as identifiers, as a struct's fields
+    /// are, rather than as a map's keys.
+    identifiers: Cell<bool>,
+    /// With [`Options::tag_as_type_annotation`], the tag entry's key and the
+    /// type annotation, read before anything else.
+    tag: Option<(&'static str, &'a str)>,
+    /// For `deserialize_any`, the node's leading arguments, read as a `seq`
+    /// or leaf value under the key `-`.
+    arguments: Option<&'a [Entry<'de>]>,
+    /// The node's properties, skipping any arguments among them.
+    properties: std::slice::Iter<'a, Entry<'de>>,
+    children: std::slice::Iter<'a, Node<'de>>,
+    /// The style of the first entry, which the rest must match unless
+    /// [`Options::mixed_map_entries`] is set.
+    style: Option<EntryStyle>,
+    value: Option<Slot<'a, 'de>>,
+}
+
+/// How a map entry is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryStyle {
+    /// A property or node named by the key.
+    Named,
+    /// A literal node with `key` and `value` fields.
+    Fields,
+    /// A literal node of the key and value.
+    Tuple,
+}
+
+fn map_entry<'a, 'de>(
+    cx: &Deserializer<'de>,
+    node: &'a Node<'de>,
+) -> Result<(EntryStyle, Slot<'a, 'de>, Slot<'a, 'de>)> {
+    if !node.is_literal() {
+        return Ok((EntryStyle::Named, Slot::Key(&node.name), Slot::Node(node)));
+    }
+    let strict = || {
+        Error::data(
+            "expected a map entry named by its key, found a literal node",
+            cx.location(node.span.start),
+        )
+    };
+    let field = |name: &str| {
+        let property = node
+            .entries
+            .iter()
+            .find(|entry| entry.name.as_deref() == Some(name));
+        let child = node.children().iter().find(|child| child.name == name);
+        property.map(Slot::Value).or(child.map(Slot::Node))
+    };
+    if let Some(key) = field("key") {
+        let value = field("value").ok_or_else(|| {
+            Error::data(
+                "map entry has a `key` but no `value`",
+                cx.location(node.span.start),
+            )
+        })?;
+        // the key `-` can't name its node, so it is written with fields
+        // among the entries named by their keys
+        if is_dash(&key) {
+            return Ok((EntryStyle::Named, key, value));
+        }
+        if !cx.opt.alternative_map_entries {
+            return Err(strict());
+        }
+        return Ok((EntryStyle::Fields, key, value));
+    }
+    if !cx.opt.alternative_map_entries {
+        return Err(strict());
+    }
+    let mut elements = node
+        .entries
+        .iter()
+        .map(|entry| match entry.name {
+            None => Ok(Slot::Value(entry)),
+            Some(_) => Err(Error::data(
+                "unexpected property in map entry",
+                cx.location(entry.span.start),
+            )),
+        })
+        .chain(node.children().iter().map(|child| Ok(Slot::Node(child))));
+    match (elements.next(), elements.next(), elements.next()) {
+        (Some(key), Some(value), None) => Ok((EntryStyle::Tuple, key?, value?)),
+        _ => Err(Error::data(
+            "expected map entry with a key and a value",
+            cx.location(node.span.start),
+        )),
+    }
+}
+
+/// Whether a map entry's key is the string `-`.
+fn is_dash(key: &Slot<'_, '_>) -> bool {
+    let entry = match key {
+        Slot::Value(entry) => entry,
+        Slot::Node(node) if node.children.is_none() => match &node.entries[..] {
+            [entry] if entry.name.is_none() => entry,
+            _ => return false,
+        },
+        _ => return false,
+    };
+    matches!(&entry.value, Literal::String(key) if key == "-")
+}
+
+impl<'a, 'de> de::MapAccess<'de> for MapAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let (offset, (style, key, value)) = if let Some((tag, ty)) = self.tag.take() {
+            self.value = Some(Slot::Tag(ty));
+            return seed.deserialize(StrDeserializer::new(tag)).map(Some);
+        } else if let Some(arguments) = self.arguments.take() {
+            self.value = Some(Slot::Arguments(arguments));
+            return seed.deserialize(StrDeserializer::new("-")).map(Some);
+        } else if let Some(entry) = self.properties.find(|entry| entry.name.is_some()) {
+            let key = Slot::Key(entry.name.as_ref().unwrap());
+            (
+                entry.span.start,
+                (EntryStyle::Named, key, Slot::Value(entry)),
+            )
+        } else if let Some(node) = self.children.next() {
+            (node.span.start, map_entry(self.cx, node)?)
+        } else {
+            if self.identifiers.get() {
+                MapCapture::fill(self.node);
+            }
+            return Ok(None);
+        };
+        if *self.style.get_or_insert(style) != style {
+            if !self.cx.opt.mixed_map_entries {
+                return Err(Error::data(
+                    "map entries must all be written in the same style",
+                    self.cx.location(offset),
+                ));
+            }
+            self.cx.warn(
+                "map entry is written in a different style from the first",
+                offset,
+            );
+        }
+        self.value = Some(value);
+        match key {
+            Slot::Key(key) => seed
+                .deserialize(KeyDeserializer {
+                    key,
+                    what: "field",
+                    identifier: Some(&self.identifiers),
+                })
+                .map(Some),
+            key => key.deserialize(self.cx, seed).map(Some),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .value
+            .take()
+            .expect("next_value called before next_key");
+        value.deserialize(self.cx, seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        let properties = self.properties.clone().filter(|e| e.name.is_some());
+        Some(
+            usize::from(self.tag.is_some())
+                + usize::from(self.arguments.is_some())
+                + properties.count()
+                + self.children.len(),
+        )
+    }
+}
+
+/// A node being read as a map, whose contents a flattened [`Document`]
+/// captures once a struct has read every entry. Serde buffers a flattened
+/// field's entries as plain values, which have lost how they were written,
+/// so the document takes its contents from here instead.
+struct MapCapture {
+    contents: Option<Document>,
+    captured: bool,
+}
+
+thread_local! {
+    /// The nodes being read as maps, innermost last.
+    static CAPTURES: RefCell<Vec<MapCapture>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Pops its node's [`MapCapture`] when dropped, even when unwinding.
+struct MapCaptureGuard;
+
+impl MapCapture {
+    fn push() -> MapCaptureGuard {
+        CAPTURES.with(|captures| {
+            captures.borrow_mut().push(MapCapture {
+                contents: None,
+                captured: false,
+            })
+        });
+        MapCaptureGuard
+    }
+
+    /// Keep the contents of the innermost node being read as a map, once
+    /// a struct has read every entry.
+    fn fill(node: &Node<'_>) {
+        CAPTURES.with(|captures| {
+            if let Some(capture) = captures.borrow_mut().last_mut() {
+                capture
+                    .contents
+                    .get_or_insert_with(|| Document::from_tree(node));
+            }
+        });
+    }
+}
+
+impl MapCaptureGuard {
+    fn captured(&self) -> bool {
+        CAPTURES.with(|captures| captures.borrow().last().is_some_and(|c| c.captured))
+    }
+}
+
+impl Drop for MapCaptureGuard {
+    fn drop(&mut self) {
+        CAPTURES.with(|captures| captures.borrow_mut().pop());
+    }
+}
+
+/// Capture the arguments and the entries with the given keys, which no
+/// other field claimed, of the struct node which was just read, for a
+/// flattened [`Document`].
+pub(crate) fn capture_map(keys: &[String]) -> Option<Document> {
+    CAPTURES.with(|captures| {
+        let mut captures = captures.borrow_mut();
+        let capture = captures.last_mut()?;
+        let mut document = capture.contents.take()?;
+        capture.captured = true;
+        document.retain_keys(keys);
+        Some(document)
+    })
+}
+
+pub fn from_str<'de, T>(s: &'de str) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    from_str_with_options(s, Options::default())
+}
+
+pub fn from_str_with_options<'de, T>(s: &'de str, opt: Options) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    let mut de = Deserializer::from_str_with_options(s, opt);
+    T::deserialize(&mut de)
+}
+
+/// Deserialize a value, along with the warnings about the input.
+pub fn from_str_with_warnings<'de, T>(s: &'de str, opt: Options) -> Result<(T, Vec<Warning>)>
+where
+    T: Deserialize<'de>,
+{
+    let mut de = Deserializer::from_str_with_options(s, opt);
+    let value = T::deserialize(&mut de)?;
+    Ok((value, de.take_warnings()))
+}
+
+pub fn from_slice<'de, T>(v: &'de [u8]) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    from_slice_with_options(v, Options::default())
+}
+
+pub fn from_slice_with_options<'de, T>(v: &'de [u8], opt: Options) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    let s = std::str::from_utf8(v).map_err(|e| {
+        let valid = std::str::from_utf8(&v[..e.valid_up_to()]).unwrap();
+        Error::syntax("invalid UTF-8", Location::in_source(valid, valid.len()))
+    })?;
+    from_str_with_options(s, opt)
+}
+
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    from_reader_with_options(reader, Options::default())
+}
+
+pub fn from_reader_with_options<R, T>(mut reader: R, opt: Options) -> Result<T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    let mut s = String::new();
+    reader.read_to_string(&mut s)?;
+    from_str_with_options(&s, opt)
+}
+
+/// An iterator over the top-level nodes of a KDL document in an
+/// `io::Read`, deserializing each as a `T`, like the elements of a `seq`
+/// at the document's root.
+///
+/// Only one node is held in memory at a time, so this suits log-like
+/// documents which values are appended to, as by a
+/// [`StreamSerializer`](crate::ser::StreamSerializer); cap its size with
+/// [`Limits::buffer_length`]. A node which doesn't deserialize as a `T` is
+/// an error, after which the next node is read; a syntax error, which is
+/// reported as soon as it is read, or an IO error ends the stream.
+///
+/// ```
+/// # use serde::Deserialize;
+/// use serde_kdl::{de::StreamDeserializer, Spanned};
+///
+/// #[derive(Deserialize)]
+/// struct Event {
+///     level: String,
+/// }
+///
+/// let log = "- level=\"info\"\n- level=\"warn\"\n".as_bytes();
+/// let mut stream = StreamDeserializer::<_, Spanned<Event>>::new(log);
+/// let event = stream.next().unwrap()?;
+/// assert_eq!((event.level.as_str(), event.span()), ("info", 0..14));
+/// let event = stream.next().unwrap()?;
+/// assert_eq!((event.level.as_str(), event.span()), ("warn", 15..29));
+/// assert!(stream.next().is_none());
+/// assert_eq!(stream.byte_offset(), log.len());
+/// # Ok::<_, serde_kdl::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct StreamDeserializer<R, T> {
+    reader: R,
+    opt: Options,
+    /// The input read but not yet deserialized.
+    buf: String,
+    /// The end of the input read, which isn't valid UTF-8, at least until
+    /// the rest of a character is read.
+    partial: Vec<u8>,
+    /// Where `buf` starts in the input.
+    base: Location,
+    /// How much of the first node in `buf` has been read.
+    scan: Option<Scan>,
+    eof: bool,
+    failed: bool,
+    output: PhantomData<fn() -> T>,
+}
+
+/// How much of the input to read at a time.
+const CHUNK: usize = 8 * 1024;
+
+impl<R, T> StreamDeserializer<R, T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    pub fn new(reader: R) -> Self {
+        Self::new_with_options(reader, Options::default())
+    }
+
+    pub fn new_with_options(reader: R, opt: Options) -> Self {
+        StreamDeserializer {
+            reader,
+            opt,
+            buf: String::new(),
+            partial: Vec::new(),
+            base: Location::start(),
+            scan: None,
+            eof: false,
+            failed: false,
+            output: PhantomData,
+        }
+    }
+
+    /// The byte offset in the input just past the last node read, where
+    /// the next one, or more input appended later, starts.
+    pub fn byte_offset(&self) -> usize {
+        self.base.offset
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read another chunk of the input into the buffer, if it has room.
+    fn fill(&mut self) -> Result {
+        let max = self.opt.limits.buffer_length;
+        let room = max
+            .saturating_sub(self.buf.len() + self.partial.len())
+            .min(CHUNK);
+        if room == 0 {
+            return Err(Error::Limit {
+                limit: Limit::BufferLength,
+                max,
+                location: Some(self.end()),
+            });
+        }
+        let mut chunk = [0; CHUNK];
+        let read = loop {
+            match self.reader.read(&mut chunk[..room]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+        self.partial.extend_from_slice(&chunk[..read]);
+        let valid = match std::str::from_utf8(&self.partial) {
+            Ok(text) => text.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        self.buf
+            .push_str(std::str::from_utf8(&self.partial[..valid]).unwrap());
+        self.partial.drain(..valid);
+        self.eof = read == 0;
+        Ok(())
+    }
+
+    /// Where the buffered text ends in the input.
+    fn end(&self) -> Location {
+        Location::in_source(&self.buf, self.buf.len()).relative_to(self.base)
+    }
+
+    /// Fail if the input read after the buffered text isn't UTF-8, and no
+    /// more of it could make it so.
+    fn check_utf8(&self) -> Result {
+        match std::str::from_utf8(&self.partial) {
+            Err(e) if self.eof || e.error_len().is_some() => {
+                Err(Error::syntax("invalid UTF-8", self.end()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Deserialize `text`, which is a single node.
+    fn deserialize(&self, text: &str) -> Result<T> {
+        let root = tree::parse(text, self.opt.limits).map_err(|e| e.relative_to(self.base))?;
+        let cx = Deserializer {
+            base: self.base,
+            ..Deserializer::from_str_with_options(text, self.opt.clone())
+        };
+        let node = &root.children()[0];
+        T::deserialize(NodeDeserializer::element(&cx, node)?)
+    }
+
+    /// Drop the first `len` bytes of the buffer, having read them.
+    fn consume(&mut self, len: usize) {
+        self.base = Location::in_source(&self.buf, len).relative_to(self.base);
+        self.buf.drain(..len);
+        self.scan = None;
+    }
+}
+
+/// How far into the first node of a [`StreamDeserializer`]'s buffer the
+/// last scan got, so that the next one carries on from there.
+#[derive(Debug, Clone, Copy)]
+struct Scan {
+    checkpoint: read::Checkpoint,
+    /// How many nodes the checkpoint is inside of.
+    depth: usize,
+}
+
+/// Where the first node in `text` ends, including its terminator, if all of
+/// it has been read, carrying on from and updating `scan`. More input could
+/// still change what was read up to the end of the text, unless `eof`.
+fn first_node_end(
+    text: &str,
+    eof: bool,
+    limits: Limits,
+    scan: &mut Option<Scan>,
+) -> Result<Option<usize>> {
+    let (mut reader, mut depth) = match *scan {
+        Some(Scan { checkpoint, depth }) => (Reader::resume(text, limits, checkpoint), depth),
+        None => (Reader::with_limits(text, limits), 0),
+    };
+    loop {
+        let event = match reader.next_event() {
+            Ok(event) => event,
+            // more input can't bring it back under a limit
+            Err(e @ Error::Limit { .. }) => return Err(e),
+            Err(e) if eof || !reader.reached_end() => return Err(e),
+            // perhaps only because the node isn't all here yet
+            Err(_) => return Ok(None),
+        };
+        if reader.reached_end() && !eof {
+            return Ok(None);
+        }
+        match event {
+            Some(Event::StartNode { .. }) => depth += 1,
+            Some(Event::EndNode { .. }) if depth == 1 => return Ok(Some(reader.offset())),
+            Some(Event::EndNode { .. }) => depth -= 1,
+            Some(_) => {}
+            None => return Ok(None),
+        }
+        if let Some(checkpoint) = reader.checkpoint() {
+            *scan = Some(Scan { checkpoint, depth });
+        }
+    }
+}
+
+impl<R, T> Iterator for StreamDeserializer<R, T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            // the text doesn't end with the input if it is followed by invalid UTF-8
+            let eof = self.eof && self.partial.is_empty();
+            let end = match first_node_end(&self.buf, eof, self.opt.limits, &mut self.scan) {
+                Ok(end) => end,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e.relative_to(self.base)));
+                }
+            };
+            if let Some(end) = end {
+                let value = self.deserialize(&self.buf[..end]);
+                self.consume(end);
+                return Some(value);
+            }
+            if let Err(e) = self.check_utf8() {
+                self.failed = true;
+                return Some(Err(e));
+            }
+            if self.eof {
+                // only whitespace and comments are left
+                let len = self.buf.len();
+                self.consume(len);
+                return None;
+            }
+            if let Err(e) = self.fill() {
+                self.failed = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! Owned KDL nodes, for keeping what isn't deserialized into anything else.
+//!
+//! A [`Document`] flattened into a struct captures the arguments,
+//! properties, and child nodes that none of the struct's other fields
+//! claim, and writes them back into the struct's node:
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Deserialize, Serialize)]
+//! struct Host {
+//!     name: String,
+//!     #[serde(flatten)]
+//!     plugins: serde_kdl::Document,
+//! }
+//!
+//! let host: Host = serde_kdl::from_str(
+//!     r#"
+//!     name "core"
+//!     (v2)cache size=64 {
+//!         path "/tmp"
+//!     }
+//!     "#,
+//! )?;
+//! assert_eq!(host.plugins.nodes[0].name, "cache");
+//! assert_eq!(host.plugins.nodes[0].ty.as_deref(), Some("v2"));
+//!
+//! let kdl = serde_kdl::to_string(&host)?;
+//! assert!(kdl.contains("(v2)cache size=64 {"));
+//! # Ok::<_, serde_kdl::Error>(())
+//! ```
+
+use {
+    crate::{de, read::Literal, tree},
+    serde::{
+        de::{Error as _, IgnoredAny, IntoDeserializer, MapAccess, SeqAccess, Visitor},
+        ser::{SerializeMap, SerializeTupleStruct},
+        Deserialize, Deserializer, Serialize, Serializer,
+    },
+    std::fmt,
+};
+
+pub(crate) const NAME: &str = "$serde_kdl::private::Document";
+
+/// A document's contents pass between it and serde-kdl as a flat sequence
+/// of tokens, a `tuple_struct` named [`NAME`] when serialized:
+///
+/// ```text
+/// contents = entry* node*
+/// entry    = ("argument" | "property" name) ("type" ty)? value
+/// value    = "string" text | "number" text | bool | "null"
+/// node     = "node" name ("type" ty)? ("leaf" | "block") contents "end"
+/// ```
+///
+/// Markers are serialized as unit variants of [`NAME`], and read as strings.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Token<'a> {
+    Marker(&'static str),
+    Text(&'a str),
+    Bool(bool),
+}
+
+/// The contents of a KDL node: its arguments and properties, and its child
+/// nodes. A whole document is the contents of its implied root node, which
+/// only has children.
+///
+/// Deserialized from a node, this is everything in that node. Flattened
+/// into a struct with `#[serde(flatten)]`, it is everything the struct's
+/// other fields don't claim, in its original order. Either way, it is
+/// written back as it was read, except that numbers keep their spelling
+/// but strings and names are requoted.
+///
+/// Captured arguments are written as arguments as long as nothing has been
+/// written to the node's children block yet, so a `Document` holding any
+/// should come before fields written as child nodes. The ugly format writes
+/// every entry as a child node, so only the human format keeps them as
+/// entries.
+///
+/// This only serializes to and deserializes from serde-kdl. It can't be
+/// deserialized through a buffer, as for untagged enums.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Document {
+    /// The arguments and properties, in order.
+    pub entries: Vec<Entry>,
+    pub nodes: Vec<Node>,
+}
+
+/// An owned KDL node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub ty: Option<String>,
+    pub name: String,
+    /// The arguments and properties, in order.
+    pub entries: Vec<Entry>,
+    /// The children block, if the node has one.
+    pub children: Option<Vec<Node>>,
+}
+
+/// A KDL argument (with no name) or property (with a name).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: Option<String>,
+    pub ty: Option<String>,
+    pub value: Value,
+}
+
+/// A KDL value, without its type annotation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    /// A number, as it is written in KDL.
+    Number(String),
+    Bool(bool),
+    Null,
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.nodes.is_empty()
+    }
+
+    /// The contents of `node`.
+    pub(crate) fn from_tree(node: &tree::Node<'_>) -> Self {
+        Document {
+            entries: node.entries.iter().map(Entry::from_tree).collect(),
+            nodes: node.children().iter().map(Node::from_tree).collect(),
+        }
+    }
+
+    /// The tokens of the document's contents.
+    pub(crate) fn tokens(&self) -> Vec<Token<'_>> {
+        let mut tokens = Vec::new();
+        push_contents(&mut tokens, &self.entries, &self.nodes);
+        tokens
+    }
+
+    /// Keep only the properties and children whose keys are in `keys`.
+    /// Arguments, and `-` children which aren't keyed by a string, are
+    /// always kept.
+    pub(crate) fn retain_keys(&mut self, keys: &[String]) {
+        let keep = |key: &str| keys.iter().any(|k| k == key);
+        self.entries
+            .retain(|entry| entry.name.as_deref().is_none_or(keep));
+        self.nodes.retain(|node| node.entry_key().is_none_or(keep));
+    }
+}
+
+impl Node {
+    fn from_tree(node: &tree::Node<'_>) -> Self {
+        Node {
+            ty: node.ty.as_deref().map(Into::into),
+            name: node.name.as_ref().into(),
+            entries: node.entries.iter().map(Entry::from_tree).collect(),
+            children: (node.children.as_ref())
+                .map(|children| children.iter().map(Node::from_tree).collect()),
+        }
+    }
+
+    /// The key of this node read as a map entry, if it is a string.
+    fn entry_key(&self) -> Option<&str> {
+        if self.name != "-" {
+            return Some(&self.name);
+        }
+        let key = (self.entries.iter())
+            .find(|entry| entry.name.as_deref() == Some("key"))
+            .or_else(|| self.entries.first().filter(|entry| entry.name.is_none()));
+        match key {
+            Some(Entry {
+                value: Value::String(key),
+                ..
+            }) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+impl Entry {
+    fn from_tree(entry: &tree::Entry<'_>) -> Self {
+        Entry {
+            name: entry.name.as_deref().map(Into::into),
+            ty: entry.ty.as_deref().map(Into::into),
+            value: match &entry.value {
+                Literal::String(s) => Value::String(s.as_ref().into()),
+                Literal::Number(n) => Value::Number((*n).into()),
+                Literal::Bool(b) => Value::Bool(*b),
+                Literal::Null => Value::Null,
+            },
+        }
+    }
+}
+
+/// Push the tokens of a node's contents.
+fn push_contents<'a>(tokens: &mut Vec<Token<'a>>, entries: &'a [Entry], nodes: &'a [Node]) {
+    for entry in entries {
+        match &entry.name {
+            Some(name) => tokens.extend([Token::Marker("property"), Token::Text(name)]),
+            None => tokens.push(Token::Marker("argument")),
+        }
+        if let Some(ty) = &entry.ty {
+            tokens.extend([Token::Marker("type"), Token::Text(ty)]);
+        }
+        match &entry.value {
+            Value::String(s) => tokens.extend([Token::Marker("string"), Token::Text(s)]),
+            Value::Number(n) => tokens.extend([Token::Marker("number"), Token::Text(n)]),
+            Value::Bool(b) => tokens.push(Token::Bool(*b)),
+            Value::Null => tokens.push(Token::Marker("null")),
+        }
+    }
+    for node in nodes {
+        tokens.extend([Token::Marker("node"), Token::Text(&node.name)]);
+        if let Some(ty) = &node.ty {
+            tokens.extend([Token::Marker("type"), Token::Text(ty)]);
+        }
+        match &node.children {
+            Some(children) => {
+                tokens.push(Token::Marker("block"));
+                push_contents(tokens, &node.entries, children);
+            }
+            None => {
+                tokens.push(Token::Marker("leaf"));
+                push_contents(tokens, &node.entries, &[]);
+            }
+        }
+        tokens.push(Token::Marker("end"));
+    }
+}
+
+impl Serialize for Token<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Token::Marker(marker) => serializer.serialize_unit_variant(NAME, 0, marker),
+            Token::Text(text) => serializer.serialize_str(text),
+            Token::Bool(b) => serializer.serialize_bool(b),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, crate::Error> for Token<'_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Token<'_> {
+    type Error = crate::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, crate::Error> {
+        match self {
+            Token::Marker(text) | Token::Text(text) => visitor.visit_str(text),
+            Token::Bool(b) => visitor.visit_bool(b),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// The document's contents, as the value of its one map entry.
+struct Contents<'a>(&'a Document);
+
+impl Serialize for Contents<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let tokens = self.0.tokens();
+        let mut contents = serializer.serialize_tuple_struct(NAME, tokens.len())?;
+        for token in &tokens {
+            contents.serialize_field(token)?;
+        }
+        contents.end()
+    }
+}
+
+impl Serialize for Document {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // a map, as that is all `#[serde(flatten)]` passes through
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry(NAME, &Contents(self))?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Document {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(NAME, DocumentVisitor)
+    }
+}
+
+struct DocumentVisitor;
+
+impl<'de> Visitor<'de> for DocumentVisitor {
+    type Value = Document;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a KDL document from serde-kdl")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Document, A::Error> {
+        let (entries, nodes) = Tokens(seq).contents(false)?;
+        Ok(Document { entries, nodes })
+    }
+
+    // flattened, as serde passes the buffered entries straight through
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Document, D::Error> {
+        deserializer.deserialize_map(FlattenedVisitor)
+    }
+}
+
+struct FlattenedVisitor;
+
+impl<'de> Visitor<'de> for FlattenedVisitor {
+    type Value = Document;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a KDL document from serde-kdl")
+    }
+
+    // the map holds the entries no other field claimed
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Document, A::Error> {
+        let mut keys = Vec::new();
+        while let Some(key) = map.next_key::<Key>()? {
+            map.next_value::<IgnoredAny>()?;
+            keys.extend(key.0);
+        }
+        de::capture_map(&keys).ok_or_else(|| {
+            A::Error::custom("a Document can only be deserialized directly from serde-kdl")
+        })
+    }
+}
+
+/// Reads a document's contents back from its tokens.
+struct Tokens<A>(A);
+
+/// A token as it is read back.
+enum TokenBuf {
+    Text(String),
+    Bool(bool),
+}
+
+impl<'de, A: SeqAccess<'de>> Tokens<A> {
+    fn next(&mut self) -> Result<Option<TokenBuf>, A::Error> {
+        self.0.next_element()
+    }
+
+    fn text(&mut self) -> Result<String, A::Error> {
+        match self.next()? {
+            Some(TokenBuf::Text(text)) => Ok(text),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Read entries and nodes up to the `end` of the node they are in, or
+    /// with `nested` unset, up to the last token.
+    fn contents(&mut self, nested: bool) -> Result<(Vec<Entry>, Vec<Node>), A::Error> {
+        let mut entries = Vec::new();
+        let mut nodes = Vec::new();
+        loop {
+            let marker = match self.next()? {
+                Some(TokenBuf::Text(marker)) => marker,
+                None if !nested => return Ok((entries, nodes)),
+                _ => return Err(unexpected()),
+            };
+            match &*marker {
+                "end" if nested => return Ok((entries, nodes)),
+                "argument" if nodes.is_empty() => entries.push(self.entry(None)?),
+                "property" if nodes.is_empty() => {
+                    let name = self.text()?;
+                    entries.push(self.entry(Some(name))?);
+                }
+                "node" => nodes.push(self.node()?),
+                _ => return Err(unexpected()),
+            }
+        }
+    }
+
+    fn entry(&mut self, name: Option<String>) -> Result<Entry, A::Error> {
+        let mut ty = None;
+        let mut token = self.next()?;
+        if matches!(&token, Some(TokenBuf::Text(marker)) if marker == "type") {
+            ty = Some(self.text()?);
+            token = self.next()?;
+        }
+        let value = match token {
+            Some(TokenBuf::Bool(b)) => Value::Bool(b),
+            Some(TokenBuf::Text(marker)) => match &*marker {
+                "string" => Value::String(self.text()?),
+                "number" => Value::Number(self.text()?),
+                "null" => Value::Null,
+                _ => return Err(unexpected()),
+            },
+            None => return Err(unexpected()),
+        };
+        Ok(Entry { name, ty, value })
+    }
+
+    fn node(&mut self) -> Result<Node, A::Error> {
+        let name = self.text()?;
+        let mut ty = None;
+        let mut marker = self.text()?;
+        if marker == "type" {
+            ty = Some(self.text()?);
+            marker = self.text()?;
+        }
+        let block = match &*marker {
+            "block" => true,
+            "leaf" => false,
+            _ => return Err(unexpected()),
+        };
+        let (entries, children) = self.contents(true)?;
+        if !block && !children.is_empty() {
+            return Err(unexpected());
+        }
+        Ok(Node {
+            ty,
+            name,
+            entries,
+            children: block.then_some(children),
+        })
+    }
+}
+
+fn unexpected<E: serde::de::Error>() -> E {
+    E::custom("expected the contents of a Document")
+}
+
+impl<'de> Deserialize<'de> for TokenBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TokenVisitor)
+    }
+}
+
+struct TokenVisitor;
+
+impl<'de> Visitor<'de> for TokenVisitor {
+    type Value = TokenBuf;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a token of a KDL document")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<TokenBuf, E> {
+        Ok(TokenBuf::Text(v.into()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<TokenBuf, E> {
+        Ok(TokenBuf::Text(v))
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<TokenBuf, E> {
+        Ok(TokenBuf::Bool(v))
+    }
+}
+
+/// A map key, if it is a string.
+struct Key(Option<String>);
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(KeyVisitor)
+    }
+}
+
+struct KeyVisitor;
+
+impl<'de> Visitor<'de> for KeyVisitor {
+    type Value = Key;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map key")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Key, E> {
+        Ok(Key(Some(v.into())))
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Key, E> {
+        Ok(Key(None))
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Key, E> {
+        Ok(Key(None))
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Key, E> {
+        Ok(Key(None))
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Key, E> {
+        Ok(Key(None))
+    }
+
+    fn visit_unit<E>(self) -> Result<Key, E> {
+        Ok(Key(None))
+    }
+}
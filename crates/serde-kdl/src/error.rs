@@ -9,6 +9,99 @@ use {
 pub enum Error {
     Custom(String),
     IO(#[from] std::io::Error),
+    /// The input is not valid KDL.
+    Syntax {
+        message: String,
+        location: Location,
+    },
+    /// The input is valid KDL, but does not match the requested Serde shape.
+    Data {
+        message: String,
+        location: Location,
+    },
+    /// The input or output exceeds one of its size limits. The location is
+    /// only known when reading.
+    Limit {
+        limit: Limit,
+        max: usize,
+        location: Option<Location>,
+    },
+}
+
+/// Which of the size limits on a document was exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Limit {
+    Depth,
+    Nodes,
+    Entries,
+    StringLength,
+    RawStringHashes,
+    NumberLength,
+    BufferLength,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limit::Depth => "nesting depth",
+            Limit::Nodes => "node count",
+            Limit::Entries => "entry count",
+            Limit::StringLength => "string length",
+            Limit::RawStringHashes => "raw string hash count",
+            Limit::NumberLength => "number length",
+            Limit::BufferLength => "buffered input length",
+        })
+    }
+}
+
+impl Error {
+    /// The location in the input that caused this error, if known.
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Error::Syntax { location, .. } | Error::Data { location, .. } => Some(*location),
+            Error::Limit { location, .. } => *location,
+            Error::Custom(_) | Error::IO(_) => None,
+        }
+    }
+
+    pub(crate) fn syntax(message: impl fmt::Display, location: Location) -> Self {
+        Error::Syntax {
+            message: message.to_string(),
+            location,
+        }
+    }
+
+    pub(crate) fn data(message: impl fmt::Display, location: Location) -> Self {
+        Error::Data {
+            message: message.to_string(),
+            location,
+        }
+    }
+
+    /// Make the error's location, which is relative to a piece of the input
+    /// starting at `base`, relative to the whole input.
+    pub(crate) fn relative_to(mut self, base: Location) -> Self {
+        match &mut self {
+            Error::Syntax { location, .. } | Error::Data { location, .. } => {
+                *location = location.relative_to(base);
+            }
+            Error::Limit {
+                location: Some(location),
+                ..
+            } => *location = location.relative_to(base),
+            _ => {}
+        }
+        self
+    }
+
+    /// Attach a location to an error which doesn't have one yet.
+    pub(crate) fn at(self, location: Location) -> Self {
+        match self {
+            Error::Custom(message) => Error::Data { message, location },
+            _ => self,
+        }
+    }
 }
 
 impl ser::Error for Error {
@@ -21,6 +114,71 @@ impl de::Error for Error {
     fn custom<T: fmt::Display>(message: T) -> Self {
         Error::Custom(message.to_string())
     }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        Error::Custom(unknown("variant", variant, expected, "variants"))
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        Error::Custom(unknown("field", field, expected, "fields"))
+    }
+}
+
+/// The message for an unknown field or variant `name`, which suggests the
+/// expected name closest to it, if any is close enough to be a misspelling.
+pub(crate) fn unknown(what: &str, name: &str, expected: &[&str], plural: &str) -> String {
+    if let Some(closest) = closest(name, expected) {
+        return format!("unknown {} `{}`, did you mean `{}`?", what, name, closest);
+    }
+    match expected {
+        [] => format!("unknown {} `{}`, there are no {}", what, name, plural),
+        [expected] => format!("unknown {} `{}`, expected `{}`", what, name, expected),
+        _ => {
+            let expected: Vec<_> = expected.iter().map(|e| format!("`{}`", e)).collect();
+            format!(
+                "unknown {} `{}`, expected one of {}",
+                what,
+                name,
+                expected.join(", ")
+            )
+        }
+    }
+}
+
+/// The candidate with the smallest edit distance from `name`, if it is at
+/// most a third of the name's length. Case and `-` for `_` are free.
+fn closest<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let normalize = |s: &str| s.to_lowercase().replace('-', "_");
+    let name = normalize(name);
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .map(|&candidate| (edit_distance(&name, &normalize(candidate)), candidate))
+        .filter(|&(distance, _)| distance <= limit)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The edit distance between `a` and `b`, in `char`s, counting swapping two
+/// adjacent characters as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // rows i - 1 and i - 2 of the distances between prefixes of `a` and `b`
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut before = previous.clone();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            row[j] = (previous[j - 1] + usize::from(a[i - 1] != b[j - 1]))
+                .min(previous[j] + 1)
+                .min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, row);
+    }
+    previous[b.len()]
 }
 
 impl fmt::Display for Error {
@@ -28,8 +186,109 @@ impl fmt::Display for Error {
         match self {
             Error::Custom(message) => f.write_str(message),
             Error::IO(_) => write!(f, "IO error"),
+            Error::Syntax { message, location } | Error::Data { message, location } => {
+                write!(f, "{} at {}", message, location)
+            }
+            Error::Limit {
+                limit,
+                max,
+                location,
+            } => {
+                write!(f, "{} exceeds the limit of {}", limit, max)?;
+                match location {
+                    Some(location) => write!(f, " at {}", location),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// A problem with the input which didn't stop it from being deserialized,
+/// such as the use of an extension the spec discourages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub message: String,
+    pub location: Location,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.location)
+    }
+}
+
+/// A position in the KDL source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Location {
+    /// Byte offset from the start of the input.
+    pub offset: usize,
+    /// One-based line number.
+    pub line: usize,
+    /// One-based column number, counted in `char`s.
+    pub column: usize,
+}
+
+impl Location {
+    pub(crate) fn in_source(src: &str, offset: usize) -> Self {
+        let before = &src[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Location {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
         }
     }
+
+    /// The location of `offset` in `src`, whose line starts are `lines`.
+    pub(crate) fn in_lines(src: &str, lines: &LineStarts, offset: usize) -> Self {
+        let line = lines.0.partition_point(|&start| start <= offset);
+        Location {
+            offset,
+            line,
+            column: src[lines.0[line - 1]..offset].chars().count() + 1,
+        }
+    }
+
+    /// The start of a text, for locations relative to it.
+    pub(crate) fn start() -> Self {
+        Location {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// This location, which is relative to a piece of the input starting at
+    /// `base`, relative to the whole input.
+    pub(crate) fn relative_to(self, base: Location) -> Self {
+        Location {
+            offset: base.offset + self.offset,
+            line: base.line + self.line - 1,
+            column: match self.line {
+                1 => base.column + self.column - 1,
+                _ => self.column,
+            },
+        }
+    }
+}
+
+/// Where each line of a text starts, to find many locations in it without
+/// counting lines from the start each time.
+#[derive(Debug)]
+pub(crate) struct LineStarts(Vec<usize>);
+
+impl LineStarts {
+    pub(crate) fn new(src: &str) -> Self {
+        let starts = src.match_indices('\n').map(|(i, _)| i + 1);
+        LineStarts(std::iter::once(0).chain(starts).collect())
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} column {}", self.line, self.column)
+    }
 }
 
 pub type Result<T = (), E = Error> = std::result::Result<T, E>;
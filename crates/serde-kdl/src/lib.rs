@@ -1,6 +1,24 @@
 pub mod de;
+pub mod document;
 pub mod error;
+pub mod node;
+#[cfg(feature = "arbitrary_precision")]
+pub mod number;
+pub mod place;
+pub mod radix;
+pub mod read;
 pub mod ser;
+mod spanned;
+mod tree;
 
-pub use error::{Error, Result};
+pub use de::{from_str, Deserializer};
+pub use document::Document;
+pub use error::{Error, Result, Warning};
+pub use node::KdlNode;
+#[cfg(feature = "arbitrary_precision")]
+pub use number::Number;
+pub use place::{Arg, Child, Prop};
+pub use radix::{Binary, Grouped, Hex, Octal};
+pub use read::Reader;
 pub use ser::{to_string, Serializer};
+pub use spanned::Spanned;
@@ -0,0 +1,317 @@
+//! Encoding types with a fixed KDL node layout.
+//!
+//! SiK maps serde's data model onto KDL, so it cannot express formats which
+//! give meaning to whether a value is an argument, a property, or a child
+//! node. [`KdlNode`] instead describes a type as a KDL node directly, reusing
+//! this crate's parser and formatters for the individual values.
+//!
+//! [`KdlNode`] is usually implemented with `#[derive(KdlNode)]` from the
+//! `serde-kdl-derive` crate, re-exported here with the `derive` feature.
+
+use {
+    crate::{
+        de::{Deserializer, NodeDeserializer, ValueDeserializer},
+        error::Location,
+        read::Limits,
+        ser::{Format, HumanFormatter, Placement, Serializer},
+        tree::{self, Node},
+        Error, Result,
+    },
+    serde::{
+        de::{DeserializeOwned, Visitor},
+        forward_to_deserialize_any, Serialize,
+    },
+};
+
+#[cfg(feature = "derive")]
+pub use serde_kdl_derive::KdlNode;
+
+/// A type which is written as a single KDL node.
+pub trait KdlNode: Sized {
+    /// The name of the node this value is written as.
+    fn node_name(&self) -> &'static str;
+
+    /// The type annotation of the node this value is written as, if any.
+    fn type_name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Write this value's arguments, properties, and children.
+    fn encode<F: Format>(&self, node: &mut NodeEncoder<'_, '_, F>) -> Result;
+
+    /// Read a value from a node.
+    ///
+    /// Any argument, property, or child not claimed from `node` is reported
+    /// as an error once this returns.
+    fn decode(node: &mut NodeDecoder<'_, '_>) -> Result<Self>;
+}
+
+/// Writes the contents of a single node. Values are written as SiK.
+pub struct NodeEncoder<'s, 'a, F: Format> {
+    ser: &'s mut Serializer<'a, F>,
+}
+
+impl<F: Format> NodeEncoder<'_, '_, F> {
+    fn field<T>(&mut self, name: Option<&'static str>, placement: Placement, value: &T) -> Result
+    where
+        T: ?Sized + Serialize,
+    {
+        let ser = &mut *self.ser;
+        ser.fmt.begin_field(ser.sink, name)?;
+        ser.fmt.set_placement(ser.sink, placement)?;
+        value.serialize(&mut *ser)?;
+        ser.fmt.end_field(ser.sink)?;
+        Ok(())
+    }
+
+    /// Write a leaf value as the next argument.
+    pub fn argument<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        self.field(None, Placement::Argument, value)
+    }
+
+    /// Write each leaf value as the next argument.
+    pub fn arguments<'v, T, I>(&mut self, values: I) -> Result
+    where
+        T: 'v + Serialize,
+        I: IntoIterator<Item = &'v T>,
+    {
+        values
+            .into_iter()
+            .try_for_each(|value| self.argument(value))
+    }
+
+    /// Write a leaf value as a property.
+    pub fn property<T: ?Sized + Serialize>(&mut self, name: &'static str, value: &T) -> Result {
+        self.field(Some(name), Placement::Property, value)
+    }
+
+    /// Write any value as a SiK child node.
+    pub fn child<T: ?Sized + Serialize>(&mut self, name: &'static str, value: &T) -> Result {
+        self.field(Some(name), Placement::Child, value)
+    }
+
+    /// Write a value as a child node with its own layout.
+    pub fn node<T: KdlNode>(&mut self, value: &T) -> Result {
+        write_node(self.ser, value)
+    }
+
+    /// Write each value as a child node with its own layout.
+    pub fn nodes<'v, T, I>(&mut self, values: I) -> Result
+    where
+        T: 'v + KdlNode,
+        I: IntoIterator<Item = &'v T>,
+    {
+        values.into_iter().try_for_each(|value| self.node(value))
+    }
+}
+
+fn write_node<F: Format, T: KdlNode>(ser: &mut Serializer<'_, F>, value: &T) -> Result {
+    ser.fmt.begin_field(ser.sink, Some(value.node_name()))?;
+    if let Some(ty) = value.type_name() {
+        ser.fmt.require_dynamic_type_annotation(ser.sink, ty)?;
+    }
+    ser.fmt.set_placement(ser.sink, Placement::Node)?;
+    ser.fmt.begin_group(ser.sink)?;
+    value.encode(&mut NodeEncoder { ser: &mut *ser })?;
+    ser.fmt.end_group(ser.sink)?;
+    ser.fmt.end_field(ser.sink)?;
+    Ok(())
+}
+
+/// Reads the contents of a single node.
+///
+/// Arguments are claimed in order, properties and children by name. Values
+/// are read as SiK.
+pub struct NodeDecoder<'a, 'de> {
+    cx: &'a Deserializer<'de>,
+    node: &'a Node<'de>,
+    used_entries: Vec<bool>,
+    used_children: Vec<bool>,
+}
+
+impl<'a, 'de> NodeDecoder<'a, 'de> {
+    fn new(cx: &'a Deserializer<'de>, node: &'a Node<'de>) -> Self {
+        NodeDecoder {
+            cx,
+            node,
+            used_entries: vec![false; node.entries.len()],
+            used_children: vec![false; node.children().len()],
+        }
+    }
+
+    fn location(&self) -> Location {
+        self.cx.location(self.node.span.start)
+    }
+
+    /// The node's name.
+    pub fn name(&self) -> &str {
+        &self.node.name
+    }
+
+    /// The node's type annotation, if any.
+    pub fn type_name(&self) -> Option<&str> {
+        self.node.ty.as_deref()
+    }
+
+    /// An error located at this node.
+    pub fn error(&self, message: impl std::fmt::Display) -> Error {
+        Error::data(message, self.location())
+    }
+
+    /// Read the next argument.
+    ///
+    /// If there are no arguments left, `Option` fields read as `None`.
+    pub fn argument<T: DeserializeOwned>(&mut self, name: &str) -> Result<T> {
+        let next = (self.node.entries.iter().enumerate())
+            .find(|&(i, entry)| entry.name.is_none() && !self.used_entries[i]);
+        match next {
+            Some((i, entry)) => {
+                self.used_entries[i] = true;
+                T::deserialize(ValueDeserializer::new(self.cx, entry))
+            }
+            None => T::deserialize(Missing {
+                message: format!("missing argument `{}`", name),
+                location: self.location(),
+            }),
+        }
+    }
+
+    /// Read all remaining arguments.
+    pub fn arguments<T: DeserializeOwned>(&mut self) -> Result<Vec<T>> {
+        let mut values = Vec::new();
+        for (i, entry) in self.node.entries.iter().enumerate() {
+            if entry.name.is_none() && !self.used_entries[i] {
+                self.used_entries[i] = true;
+                values.push(T::deserialize(ValueDeserializer::new(self.cx, entry))?);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Read a property. If it is given multiple times, the last one wins.
+    ///
+    /// If the property is absent, `Option` fields read as `None`.
+    pub fn property<T: DeserializeOwned>(&mut self, name: &str) -> Result<T> {
+        let mut found = None;
+        for (i, entry) in self.node.entries.iter().enumerate() {
+            if entry.name.as_deref() == Some(name) {
+                self.used_entries[i] = true;
+                found = Some(entry);
+            }
+        }
+        match found {
+            Some(entry) => T::deserialize(ValueDeserializer::new(self.cx, entry)),
+            None => T::deserialize(Missing {
+                message: format!("missing property `{}`", name),
+                location: self.location(),
+            }),
+        }
+    }
+
+    /// Read a SiK child node.
+    ///
+    /// If the child is absent, `Option` fields read as `None`.
+    pub fn child<T: DeserializeOwned>(&mut self, name: &str) -> Result<T> {
+        let next = (self.node.children().iter().enumerate())
+            .find(|&(i, child)| child.name == name && !self.used_children[i]);
+        match next {
+            Some((i, child)) => {
+                self.used_children[i] = true;
+                T::deserialize(NodeDeserializer::new(self.cx, child))
+            }
+            None => T::deserialize(Missing {
+                message: format!("missing child node `{}`", name),
+                location: self.location(),
+            }),
+        }
+    }
+
+    /// Read all remaining child nodes with their own layout.
+    pub fn nodes<T: KdlNode>(&mut self) -> Result<Vec<T>> {
+        let mut values = Vec::new();
+        for (i, child) in self.node.children().iter().enumerate() {
+            if !self.used_children[i] {
+                self.used_children[i] = true;
+                values.push(read_node(self.cx, child)?);
+            }
+        }
+        Ok(values)
+    }
+
+    fn finish(&self) -> Result {
+        let entries = self.node.entries.iter().zip(&self.used_entries);
+        if let Some((entry, _)) = entries.clone().find(|(_, &used)| !used) {
+            let message = match &entry.name {
+                Some(name) => format!("unexpected property `{}`", name),
+                None => "unexpected argument".to_string(),
+            };
+            return Err(Error::data(message, self.cx.location(entry.span.start)));
+        }
+        let children = self.node.children().iter().zip(&self.used_children);
+        if let Some((child, _)) = children.clone().find(|(_, &used)| !used) {
+            return Err(Error::data(
+                format!("unexpected child node `{}`", child.name),
+                self.cx.location(child.span.start),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn read_node<T: KdlNode>(cx: &Deserializer<'_>, node: &Node<'_>) -> Result<T> {
+    let mut decoder = NodeDecoder::new(cx, node);
+    let value = T::decode(&mut decoder)?;
+    decoder.finish()?;
+    Ok(value)
+}
+
+/// Stands in for an absent value, so that `Option`s read as `None`.
+struct Missing {
+    message: String,
+    location: Location,
+}
+
+impl<'de> serde::Deserializer<'de> for Missing {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::data(self.message, self.location))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_none()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Read a document consisting of a single node.
+pub fn from_str<T: KdlNode>(s: &str) -> Result<T> {
+    let cx = Deserializer::from_str(s);
+    let root = tree::parse(s, Limits::default())?;
+    match root.children() {
+        [node] => read_node(&cx, node),
+        [] => Err(Error::data(
+            "expected a root node, found empty document",
+            cx.location(s.len()),
+        )),
+        [_, node, ..] => Err(Error::data(
+            "expected a single root node",
+            cx.location(node.span.start),
+        )),
+    }
+}
+
+/// Write a value as a document consisting of a single node.
+pub fn to_string<T: KdlNode>(value: &T) -> Result<String> {
+    let mut buf = String::new();
+    let mut ser = Serializer::new(&mut buf, HumanFormatter::default());
+    ser.fmt.begin_group(ser.sink)?;
+    write_node(&mut ser, value)?;
+    ser.fmt.end_group(ser.sink)?;
+    Ok(buf)
+}
@@ -0,0 +1,209 @@
+//! Numbers of any size and precision, kept as their text.
+//!
+//! With the `arbitrary_precision` feature, serde-kdl reads every number
+//! through `deserialize_any` as the private struct serde_json uses for the
+//! same feature, so a [`Number`], and serde_json's and `rust_decimal`'s
+//! arbitrary precision types, read the number's text rather than an `f64` or
+//! `i128`:
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! use serde_kdl::Number;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Order {
+//!     id: Number,
+//!     total: Number,
+//! }
+//!
+//! let kdl = "\nid 1234567890123456789012345678901234567890\ntotal 19.99\n";
+//! let order: Order = serde_kdl::from_str(kdl)?;
+//! assert_eq!(order.total.as_str(), "19.99");
+//! assert_eq!(serde_kdl::to_string(&order)?, kdl);
+//! # Ok::<_, serde_kdl::Error>(())
+//! ```
+//!
+//! The text is in the decimal notation JSON uses: `_` separators, a leading
+//! `+`, and leading zeros are dropped, and an integer written in another
+//! radix is read in decimal. A number which an `i64`, `u64` or `f64` holds
+//! exactly, keeping its text, is read as that instead, so only one which
+//! none does is the struct. A type that reads numbers through
+//! `deserialize_any`, but not this struct, such as serde_json's `Value`
+//! without its own `arbitrary_precision` feature, sees that one as a map.
+//! As with serde_json, so do the fields of `#[serde(flatten)]` and untagged
+//! and internally tagged enums, which serde buffers: `19.990` or `1e400`
+//! fails to read as an `f64` there, where it wouldn't without the feature.
+
+use {
+    crate::read::{self, is_valid_number},
+    serde::{
+        de::{self, Unexpected},
+        ser::SerializeStruct,
+        Deserialize, Deserializer, Serialize, Serializer,
+    },
+    std::{fmt, str::FromStr},
+};
+
+/// The name of the struct, and of its only field, which holds a number's
+/// text. It is serde_json's, so that its numbers, and those of types built
+/// on it, are read and written by serde-kdl as numbers too.
+pub(crate) const TOKEN: &str = "$serde_json::private::Number";
+
+/// A KDL number, as decimal text of any size and precision.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Number {
+    text: String,
+}
+
+impl Number {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The number as an `i64`, if it is an integer in range.
+    pub fn as_i64(&self) -> Option<i64> {
+        read::Number::parse(&self.text).to_integer()
+    }
+
+    /// The number as a `u64`, if it is an integer in range.
+    pub fn as_u64(&self) -> Option<u64> {
+        read::Number::parse(&self.text).to_integer()
+    }
+
+    /// The number as an `f64`, rounding if need be.
+    pub fn as_f64(&self) -> f64 {
+        read::Number::parse(&self.text).to_float()
+    }
+}
+
+/// The text of a KDL number in the decimal notation JSON uses, or `None` if
+/// it has none: `#inf`, `#-inf`, `#nan`, and integers in other radixes too
+/// large for a `u128`.
+pub(crate) fn to_json(text: &str) -> Option<String> {
+    if let read::Number::Integer {
+        negative,
+        magnitude,
+    } = read::Number::parse(text)
+    {
+        let sign = if negative && magnitude != 0 { "-" } else { "" };
+        return Some(format!("{}{}", sign, magnitude));
+    }
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", text.strip_prefix('+').unwrap_or(text)),
+    };
+    let radix = matches!(unsigned.get(..2), Some("0x" | "0o" | "0b"));
+    if radix || !unsigned.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let digits = unsigned.replace('_', "");
+    let integer = digits.find(&['.', 'e', 'E'][..]).unwrap_or(digits.len());
+    let zeros = digits[..integer - 1].len() - digits[..integer - 1].trim_start_matches('0').len();
+    Some(format!("{}{}", sign, &digits[zeros..]))
+}
+
+/// The JSON text of a number which no `i64`, `u64` or `f64` holds
+/// exactly, to read as the private struct rather than as a primitive. Only
+/// these can't be read from the values serde buffers.
+#[cfg(feature = "arbitrary_precision")]
+pub(crate) fn to_exact_json(text: &str) -> Option<String> {
+    let json = to_json(text)?;
+    let number = read::Number::parse(text);
+    let exact = number.to_integer::<i64>().is_some()
+        || number.to_integer::<u64>().is_some()
+        || format!("{:?}", number.to_float()) == json;
+    (!exact).then_some(json)
+}
+
+impl FromStr for Number {
+    type Err = crate::Error;
+
+    /// Parse a number written in KDL, which must be finite.
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match is_valid_number(s).then(|| to_json(s)).flatten() {
+            Some(text) => Ok(Number { text }),
+            None => Err(crate::Error::Custom(format!("invalid number `{}`", s))),
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+macro_rules! from_integer {
+    ($($T:ident)*) => {$(
+        impl From<$T> for Number {
+            fn from(v: $T) -> Self {
+                Number { text: v.to_string() }
+            }
+        }
+    )*};
+}
+
+from_integer! {
+    i8 i16 i32 i64 i128 isize
+    u8 u16 u32 u64 u128 usize
+}
+
+impl Serialize for Number {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct(TOKEN, 1)?;
+        s.serialize_field(TOKEN, &self.text)?;
+        s.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NumberVisitor)
+    }
+}
+
+struct NumberVisitor;
+
+impl<'de> de::Visitor<'de> for NumberVisitor {
+    type Value = Number;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a number")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Number, E> {
+        Ok(v.into())
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Number, E> {
+        Ok(v.into())
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Number, E> {
+        Ok(v.into())
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Number, E> {
+        Ok(v.into())
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Number, E> {
+        match v.is_finite() {
+            // `Debug` keeps the `.0` of a whole number
+            true => Ok(Number {
+                text: format!("{:?}", v),
+            }),
+            false => Err(E::invalid_value(Unexpected::Float(v), &self)),
+        }
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Number, A::Error> {
+        match map.next_key::<String>()? {
+            Some(key) if key == TOKEN => {
+                let text: String = map.next_value()?;
+                text.parse().map_err(de::Error::custom)
+            }
+            _ => Err(de::Error::custom("expected a number")),
+        }
+    }
+}
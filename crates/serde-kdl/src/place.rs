@@ -0,0 +1,139 @@
+//! Wrappers which control where a struct field is placed in its node.
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! use serde_kdl::{Arg, Child, Prop};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Config {
+//!     listener: Listener,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Listener {
+//!     name: Arg<String>,
+//!     routes: Child<Vec<String>>,
+//!     port: Prop<u16>,
+//! }
+//!
+//! let config = Config {
+//!     listener: Listener {
+//!         name: Arg("web".into()),
+//!         routes: Child(vec!["/".into(), "/api".into()]),
+//!         port: Prop(80),
+//!     },
+//! };
+//! let kdl = serde_kdl::to_string(&config)?;
+//! assert_eq!(
+//!     kdl,
+//!     "\nlistener r\"web\" port=80 {\n    routes r\"/\" r\"/api\"\n}\n"
+//! );
+//! # Ok::<_, serde_kdl::Error>(())
+//! ```
+//!
+//! Placement is honoured by [`HumanFormatter`](crate::ser::HumanFormatter)
+//! wherever the resulting KDL reads back as the same value:
+//!
+//! - [`Arg`] fields are written as KDL Arguments if they are leaf values and
+//!   all fields before them are also [`Arg`]s, as arguments are read back as
+//!   fields in order.
+//! - [`Prop`] fields are written as KDL Properties if they are leaf values,
+//!   even when they follow fields written as child nodes.
+//! - [`Child`] fields are always written as child nodes.
+//!
+//! Otherwise, fields are placed as if unwrapped. The wrappers are transparent
+//! when deserializing, as fields are accepted in any of these positions.
+
+use {
+    serde::{de, Deserialize, Deserializer},
+    std::{fmt, marker::PhantomData},
+};
+
+pub(crate) const ARG: &str = "$serde_kdl::private::Arg";
+pub(crate) const PROP: &str = "$serde_kdl::private::Prop";
+pub(crate) const CHILD: &str = "$serde_kdl::private::Child";
+
+/// Whether `name` is that of one of this crate's newtype wrappers, which
+/// are transparent rather than `newtype_struct`s of their own.
+pub(crate) fn is_wrapper(name: &str) -> bool {
+    matches!(name, ARG | PROP | CHILD) || crate::radix::is_wrapper(name)
+}
+
+/// Other formats see the wrappers as plain newtype structs.
+pub(crate) struct NewtypeVisitor<T>(pub PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> de::Visitor<'de> for NewtypeVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a newtype struct")
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        T::deserialize(deserializer)
+    }
+}
+
+/// Define newtype wrappers, each serialized as a `newtype_struct` with the
+/// given private name.
+macro_rules! newtype_wrapper {
+    ($($(#[$meta:meta])* $Name:ident = $NAME:path;)*) => {$(
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $Name<T>(pub T);
+
+        impl<T> $Name<T> {
+            pub fn into_inner(self) -> T {
+                self.0
+            }
+        }
+
+        impl<T> From<T> for $Name<T> {
+            fn from(value: T) -> Self {
+                $Name(value)
+            }
+        }
+
+        impl<T> ::std::ops::Deref for $Name<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T> ::std::ops::DerefMut for $Name<T> {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+
+        impl<T: ::serde::Serialize> ::serde::Serialize for $Name<T> {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct($NAME, &self.0)
+            }
+        }
+
+        impl<'de, T: ::serde::Deserialize<'de>> ::serde::Deserialize<'de> for $Name<T> {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer
+                    .deserialize_newtype_struct(
+                        $NAME,
+                        $crate::place::NewtypeVisitor(::std::marker::PhantomData),
+                    )
+                    .map($Name)
+            }
+        }
+    )*};
+}
+
+pub(crate) use newtype_wrapper;
+
+newtype_wrapper! {
+    /// A struct field which should be written as a KDL Argument.
+    Arg = ARG;
+    /// A struct field which should be written as a KDL Property.
+    Prop = PROP;
+    /// A struct field which should be written as a child node.
+    Child = CHILD;
+}
@@ -0,0 +1,57 @@
+//! Wrappers which control how an integer is written.
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! use serde_kdl::{Grouped, Hex, Octal};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Config {
+//!     file: File,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct File {
+//!     mode: Octal<u32>,
+//!     flags: Hex<u16>,
+//!     size: Grouped<u64>,
+//! }
+//!
+//! let config = Config {
+//!     file: File {
+//!         mode: Octal(0o755),
+//!         flags: Hex(0x80FF),
+//!         size: Grouped(1048576),
+//!     },
+//! };
+//! let kdl = serde_kdl::to_string(&config)?;
+//! assert_eq!(kdl, "\nfile mode=0o755 flags=0x80FF size=1_048_576\n");
+//! # Ok::<_, serde_kdl::Error>(())
+//! ```
+//!
+//! Styles are honoured by [`HumanFormatter`](crate::ser::HumanFormatter);
+//! other formatters write integers in decimal. The wrappers are transparent
+//! when deserializing, as integers are read in any radix, and around values
+//! which aren't integers.
+
+use crate::place::newtype_wrapper;
+
+pub(crate) const GROUPED: &str = "$serde_kdl::private::Grouped";
+pub(crate) const HEX: &str = "$serde_kdl::private::Hex";
+pub(crate) const OCTAL: &str = "$serde_kdl::private::Octal";
+pub(crate) const BINARY: &str = "$serde_kdl::private::Binary";
+
+pub(crate) fn is_wrapper(name: &str) -> bool {
+    matches!(name, GROUPED | HEX | OCTAL | BINARY)
+}
+
+newtype_wrapper! {
+    /// An integer which should be written in decimal with its digits in
+    /// groups of three, like `1_000_000`.
+    Grouped = GROUPED;
+    /// An integer which should be written in hexadecimal, like `0xFF`.
+    Hex = HEX;
+    /// An integer which should be written in octal, like `0o755`.
+    Octal = OCTAL;
+    /// An integer which should be written in binary, like `0b1010`.
+    Binary = BINARY;
+}
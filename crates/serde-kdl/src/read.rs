@@ -0,0 +1,1231 @@
+//! A pull parser for KDL text, for scanning a document without
+//! deserializing it. The deserializer reads documents with it, too.
+//!
+//! ```
+//! use serde_kdl::read::{Event, Reader};
+//!
+//! let kdl = r#"
+//!     package "serde-kdl"
+//!     // only the dependencies are needed
+//!     dependencies {
+//!         serde "1.0" optional=#true
+//!     }
+//! "#;
+//! let mut depth = 0;
+//! let mut names = Vec::new();
+//! for event in Reader::new(kdl) {
+//!     match event? {
+//!         Event::StartNode { name, .. } if depth == 1 => names.push(name),
+//!         Event::StartChildren { .. } => depth += 1,
+//!         Event::EndChildren { .. } => depth -= 1,
+//!         _ => {}
+//!     }
+//! }
+//! assert_eq!(names, ["serde"]);
+//! # Ok::<_, serde_kdl::Error>(())
+//! ```
+
+use {
+    crate::{
+        error::{Limit, Location},
+        Error, Result,
+    },
+    std::{borrow::Cow, collections::VecDeque, ops::Range},
+};
+
+/// A byte range in the KDL source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.start..span.end
+    }
+}
+
+/// A KDL value, without its type annotation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal<'de> {
+    String(Cow<'de, str>),
+    /// The number's source text; it is only parsed once we know the target type.
+    Number(&'de str),
+    Bool(bool),
+    Null,
+}
+
+/// A piece of a KDL document, in the order it is written.
+///
+/// Each node is a `StartNode`, its arguments and properties, its children
+/// block if it has one, and then an `EndNode`. Spans cover the source text
+/// of each piece, except that an `EndNode` is the empty span just before the
+/// node's terminator.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Event<'de> {
+    StartNode {
+        ty: Option<Cow<'de, str>>,
+        name: Cow<'de, str>,
+        span: Span,
+    },
+    Argument {
+        ty: Option<Cow<'de, str>>,
+        value: Literal<'de>,
+        span: Span,
+    },
+    Property {
+        name: Cow<'de, str>,
+        ty: Option<Cow<'de, str>>,
+        value: Literal<'de>,
+        span: Span,
+    },
+    StartChildren {
+        span: Span,
+    },
+    EndChildren {
+        span: Span,
+    },
+    EndNode {
+        span: Span,
+    },
+    /// A `//` or `/* */` comment, or a node, entry, or children block
+    /// commented out with `/-`, as written. A comment inside a node's name
+    /// or an entry comes after that piece.
+    Comment {
+        text: &'de str,
+        span: Span,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Between nodes, at the document root or in a children block.
+    Nodes,
+    /// After a node's name, reading its arguments and properties.
+    Entries,
+    /// After a node's children block, expecting the node terminator.
+    AfterChildren,
+    Done,
+}
+
+/// Caps on the size of a document, for reading untrusted input.
+///
+/// Exceeding one is an [`Error::Limit`] naming it. Only the nesting depth
+/// is limited by default, to what can be deserialized without overflowing
+/// the stack; the rest are unlimited.
+///
+/// ```
+/// # use serde_kdl::read::Limits;
+/// let limits = Limits::new().nodes(10_000).string_length(64 * 1024);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Limits {
+    /// How deeply children blocks may nest.
+    pub depth: usize,
+    /// How many nodes the document may have, at any depth, including those
+    /// commented out with a slashdash.
+    pub nodes: usize,
+    /// How many arguments and properties, together, a node may have.
+    pub entries: usize,
+    /// How long a string or identifier may be, in bytes, after processing
+    /// its escapes.
+    pub string_length: usize,
+    /// How many `#`s may delimit a raw string.
+    pub raw_string_hashes: usize,
+    /// How long a number may be, in bytes, as written.
+    pub number_length: usize,
+    /// How much input a [`StreamDeserializer`](crate::de::StreamDeserializer)
+    /// may hold at once, in bytes, while reading a node and the whitespace
+    /// and comments before it. Other readers have all of their input already.
+    pub buffer_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            depth: 128,
+            nodes: usize::MAX,
+            entries: usize::MAX,
+            string_length: usize::MAX,
+            raw_string_hashes: usize::MAX,
+            number_length: usize::MAX,
+            buffer_length: usize::MAX,
+        }
+    }
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// No limits at all, not even on nesting depth.
+    pub fn none() -> Self {
+        Self {
+            depth: usize::MAX,
+            ..Self::default()
+        }
+    }
+
+    pub fn depth(mut self, max: usize) -> Self {
+        self.depth = max;
+        self
+    }
+
+    pub fn nodes(mut self, max: usize) -> Self {
+        self.nodes = max;
+        self
+    }
+
+    pub fn entries(mut self, max: usize) -> Self {
+        self.entries = max;
+        self
+    }
+
+    pub fn string_length(mut self, max: usize) -> Self {
+        self.string_length = max;
+        self
+    }
+
+    pub fn raw_string_hashes(mut self, max: usize) -> Self {
+        self.raw_string_hashes = max;
+        self
+    }
+
+    pub fn number_length(mut self, max: usize) -> Self {
+        self.number_length = max;
+        self
+    }
+
+    pub fn buffer_length(mut self, max: usize) -> Self {
+        self.buffer_length = max;
+        self
+    }
+}
+
+/// A streaming KDL parser, producing a flat sequence of [`Event`]s.
+///
+/// Accepts KDL v1, plus the unambiguous v2 spellings (`#true`, `#"raw"#`, ...).
+/// As an iterator, it stops after the first error.
+#[derive(Debug)]
+pub struct Reader<'de> {
+    src: &'de str,
+    pos: usize,
+    state: State,
+    depth: usize,
+    limits: Limits,
+    /// How many nodes have started so far.
+    nodes: usize,
+    /// How many entries the current node has so far.
+    entries: usize,
+    spaced: bool,
+    /// Whether a slashdash comment is being skipped.
+    skipping: bool,
+    /// The nodes commented out with a slashdash, outside of any others.
+    skipped_nodes: Vec<Span>,
+    /// The comments skipped over but not yet returned.
+    comments: VecDeque<Span>,
+    /// Whether the reader has looked at the end of its input, so that more
+    /// input could have changed what it read.
+    reached_end: bool,
+}
+
+/// Where a [`Reader`] is between two events, to carry on from there once
+/// more of its input has been read.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Checkpoint {
+    pos: usize,
+    state: State,
+    depth: usize,
+    nodes: usize,
+    entries: usize,
+    spaced: bool,
+}
+
+pub(crate) fn is_newline(c: char) -> bool {
+    matches!(
+        c,
+        '\n' | '\r' | '\u{85}' | '\u{0C}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+pub(crate) fn is_whitespace(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | ' ' | '\u{A0}' | '\u{1680}' | '\u{2000}'
+            ..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' | '\u{FEFF}'
+    )
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c > ' ' && !is_whitespace(c) && !is_newline(c) && !r#"\/(){}<>;[]=,""#.contains(c)
+}
+
+fn looks_like_number(s: &str) -> bool {
+    let s = s.strip_prefix(&['+', '-'][..]).unwrap_or(s);
+    s.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn is_digits(s: &str, radix: u32) -> bool {
+    s.starts_with(|c: char| c.is_digit(radix)) && s.chars().all(|c| c == '_' || c.is_digit(radix))
+}
+
+pub(crate) fn is_valid_number(s: &str) -> bool {
+    let s = s.strip_prefix(&['+', '-'][..]).unwrap_or(s);
+    if let Some(digits) = s.strip_prefix("0x") {
+        return is_digits(digits, 16);
+    }
+    if let Some(digits) = s.strip_prefix("0o") {
+        return is_digits(digits, 8);
+    }
+    if let Some(digits) = s.strip_prefix("0b") {
+        return is_digits(digits, 2);
+    }
+    let (mantissa, exponent) = match s.find(&['e', 'E'][..]) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], Some(&mantissa[i + 1..])),
+        None => (mantissa, None),
+    };
+    is_digits(integer, 10)
+        && fraction.is_none_or(|f| is_digits(f, 10))
+        && exponent.is_none_or(|e| is_digits(e.strip_prefix(&['+', '-'][..]).unwrap_or(e), 10))
+}
+
+macro_rules! parse_float {
+    ($($name:ident -> $T:ident),*) => {$(
+        /// Parse a valid KDL number as a float, rounding it only once, or
+        /// `None` if it is finite but too large for the type.
+        pub(crate) fn $name(text: &str) -> Option<$T> {
+            let float = match Number::parse(text) {
+                Number::Integer {
+                    negative,
+                    magnitude,
+                } => {
+                    let float = magnitude as $T;
+                    if negative {
+                        -float
+                    } else {
+                        float
+                    }
+                }
+                // `#inf`, `#-inf`, and `#nan`
+                Number::Float(float) if text.starts_with('#') => return Some(float as $T),
+                Number::Float(_) => text.replace('_', "").parse().expect("valid KDL number"),
+                Number::BigInteger(float) => float as $T,
+            };
+            float.is_finite().then_some(float)
+        }
+    )*};
+}
+
+/// The value of a valid KDL number, once its target type asks for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Number {
+    /// An integer in any radix, as its sign and magnitude.
+    Integer { negative: bool, magnitude: u128 },
+    /// An integer too large even for a `u128`, as an `f64`.
+    BigInteger(f64),
+    /// A number with a fraction or exponent, or one of `#inf`, `#-inf`, and
+    /// `#nan`.
+    Float(f64),
+}
+
+impl Number {
+    pub(crate) fn parse(text: &str) -> Self {
+        match text {
+            "#inf" => return Number::Float(f64::INFINITY),
+            "#-inf" => return Number::Float(f64::NEG_INFINITY),
+            "#nan" => return Number::Float(f64::NAN),
+            _ => {}
+        }
+        let negative = text.starts_with('-');
+        let sign = if negative { -1.0 } else { 1.0 };
+        let unsigned = text.strip_prefix(&['+', '-'][..]).unwrap_or(text);
+        let (radix, digits) = match unsigned.get(..2) {
+            Some("0x") => (16, &unsigned[2..]),
+            Some("0o") => (8, &unsigned[2..]),
+            Some("0b") => (2, &unsigned[2..]),
+            _ => (10, unsigned),
+        };
+        let digits = digits.replace('_', "");
+        if radix == 10 && digits.contains(&['.', 'e', 'E'][..]) {
+            return Number::Float(sign * digits.parse::<f64>().expect("valid KDL number"));
+        }
+        match u128::from_str_radix(&digits, radix) {
+            Ok(magnitude) => Number::Integer {
+                negative,
+                magnitude,
+            },
+            Err(_) if radix == 10 => Number::BigInteger(sign * digits.parse::<f64>().unwrap()),
+            Err(_) => Number::BigInteger(
+                sign * digits.chars().fold(0.0, |float, digit| {
+                    float * f64::from(radix) + f64::from(digit.to_digit(radix).unwrap())
+                }),
+            ),
+        }
+    }
+
+    /// The integer as a `T`, if it is one and in range.
+    pub(crate) fn to_integer<T: TryFrom<i128> + TryFrom<u128>>(self) -> Option<T> {
+        match self {
+            Number::Integer {
+                negative: true,
+                magnitude,
+            } => T::try_from(0i128.checked_sub_unsigned(magnitude)?).ok(),
+            Number::Integer {
+                negative: false,
+                magnitude,
+            } => T::try_from(magnitude).ok(),
+            Number::BigInteger(_) | Number::Float(_) => None,
+        }
+    }
+
+    parse_float! {
+        parse_f32 -> f32,
+        parse_f64 -> f64
+    }
+
+    /// The number as an `f64`, rounding if need be.
+    #[cfg(feature = "arbitrary_precision")]
+    pub(crate) fn to_float(self) -> f64 {
+        match self {
+            Number::Integer {
+                negative,
+                magnitude,
+            } => {
+                let float = magnitude as f64;
+                if negative {
+                    -float
+                } else {
+                    float
+                }
+            }
+            Number::BigInteger(float) | Number::Float(float) => float,
+        }
+    }
+}
+
+/// Find `delimiter` in the body of a quoted string, skipping over escapes.
+fn find_unescaped(body: &str, delimiter: &str) -> Option<usize> {
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if body[i..].starts_with(delimiter) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Process the escapes in the body of a quoted string.
+///
+/// On error, returns the message and the byte offset of the bad escape.
+fn unescape(body: &str) -> Result<Cow<'_, str>, (&'static str, usize)> {
+    if !body.contains('\\') {
+        return Ok(Cow::Borrowed(body));
+    }
+    let mut s = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(i) = rest.find('\\') {
+        s.push_str(&rest[..i]);
+        let escape = body.len() - rest.len() + i;
+        let mut chars = rest[i + 1..].chars();
+        let c = chars.next().ok_or(("invalid escape in string", escape))?;
+        rest = chars.as_str();
+        match c {
+            'n' => s.push('\n'),
+            'r' => s.push('\r'),
+            't' => s.push('\t'),
+            '\\' => s.push('\\'),
+            '/' => s.push('/'),
+            '"' => s.push('"'),
+            'b' => s.push('\u{08}'),
+            'f' => s.push('\u{0C}'),
+            's' => s.push(' '),
+            'u' => {
+                let close = rest.find('}').filter(|_| rest.starts_with('{'));
+                let c = close
+                    .filter(|&i| (2..=7).contains(&i))
+                    .and_then(|i| u32::from_str_radix(&rest[1..i], 16).ok())
+                    .and_then(char::from_u32)
+                    .ok_or(("invalid unicode escape", escape))?;
+                s.push(c);
+                rest = &rest[close.unwrap() + 1..];
+            }
+            c if is_whitespace(c) || is_newline(c) => {
+                rest = rest.trim_start_matches(|c| is_whitespace(c) || is_newline(c));
+            }
+            _ => return Err(("invalid escape in string", escape)),
+        }
+    }
+    s.push_str(rest);
+    Ok(Cow::Owned(s))
+}
+
+/// Split on KDL newlines, treating CRLF as a single newline.
+fn split_lines(s: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if is_newline(c) {
+            lines.push(&s[start..i]);
+            start = i + c.len_utf8();
+            if c == '\r' && chars.next_if(|&(_, c)| c == '\n').is_some() {
+                start += 1;
+            }
+        }
+    }
+    lines.push(&s[start..]);
+    lines
+}
+
+/// Dedent the body of a multi-line string. The first line is the (empty)
+/// remainder of the opening line, and the last line is the indentation
+/// before the closing quotes, which is removed from every other line.
+fn dedent(body: &str) -> Result<String, &'static str> {
+    let lines = split_lines(body);
+    let (indent, lines) = match lines.split_last() {
+        Some((indent, [_, lines @ ..])) => (*indent, lines),
+        _ => return Err("multi-line string must start with a newline"),
+    };
+    if !indent.chars().all(is_whitespace) {
+        return Err("multi-line string must end with its closing quotes on their own line");
+    }
+    let mut s = String::with_capacity(body.len());
+    for (i, line) in lines.iter().enumerate() {
+        if i != 0 {
+            s.push('\n');
+        }
+        if !line.chars().all(is_whitespace) {
+            s.push_str(line.strip_prefix(indent).ok_or(
+                "multi-line string lines must start with the same indentation as its closing quotes",
+            )?);
+        }
+    }
+    Ok(s)
+}
+
+impl<'de> Reader<'de> {
+    pub fn new(src: &'de str) -> Self {
+        Self::with_limits(src, Limits::default())
+    }
+
+    /// A reader which fails once the document exceeds any of `limits`.
+    pub fn with_limits(src: &'de str, limits: Limits) -> Self {
+        Reader {
+            src,
+            pos: src
+                .strip_prefix('\u{FEFF}')
+                .map_or(0, |_| '\u{FEFF}'.len_utf8()),
+            state: State::Nodes,
+            depth: 0,
+            limits,
+            nodes: 0,
+            entries: 0,
+            spaced: false,
+            skipping: false,
+            skipped_nodes: Vec::new(),
+            comments: VecDeque::new(),
+            reached_end: false,
+        }
+    }
+
+    /// A reader which carries on from `checkpoint` in `src`, which starts
+    /// with the input the checkpoint was taken in.
+    pub(crate) fn resume(src: &'de str, limits: Limits, checkpoint: Checkpoint) -> Self {
+        let Checkpoint {
+            pos,
+            state,
+            depth,
+            nodes,
+            entries,
+            spaced,
+        } = checkpoint;
+        Reader {
+            pos,
+            state,
+            depth,
+            nodes,
+            entries,
+            spaced,
+            ..Self::with_limits(src, limits)
+        }
+    }
+
+    /// Where the reader is, unless it is partway through returning the
+    /// comments before an event.
+    pub(crate) fn checkpoint(&self) -> Option<Checkpoint> {
+        self.comments.is_empty().then_some(Checkpoint {
+            pos: self.pos,
+            state: self.state,
+            depth: self.depth,
+            nodes: self.nodes,
+            entries: self.entries,
+            spaced: self.spaced,
+        })
+    }
+
+    /// Whether the reader has looked at the end of its input, so that an
+    /// event or error might be different with more of it.
+    pub(crate) fn reached_end(&self) -> bool {
+        self.reached_end
+    }
+
+    fn rest(&self) -> &'de str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    /// The character `skip` bytes ahead.
+    fn peek_at(&mut self, skip: usize) -> Option<char> {
+        let c = self.rest()[skip..].chars().next();
+        self.reached_end |= c.is_none();
+        c
+    }
+
+    /// Whether the input goes on with `pattern`, `skip` bytes ahead.
+    fn looking_at(&mut self, skip: usize, pattern: &str) -> bool {
+        let rest = &self.rest()[skip..];
+        self.reached_end |= rest.len() < pattern.len() && pattern.starts_with(rest);
+        rest.starts_with(pattern)
+    }
+
+    fn starts_with(&mut self, pattern: &str) -> bool {
+        self.looking_at(0, pattern)
+    }
+
+    /// An error for a string or comment which doesn't end before the input.
+    fn unterminated(&mut self, message: &str, start: usize) -> Error {
+        self.reached_end = true;
+        self.error_at(message, start)
+    }
+
+    fn bump(&mut self, c: char) {
+        self.pos += c.len_utf8();
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> Error {
+        self.error_at(message, self.pos)
+    }
+
+    fn error_at(&self, message: impl std::fmt::Display, offset: usize) -> Error {
+        Error::syntax(message, Location::in_source(self.src, offset))
+    }
+
+    /// Fail with [`Error::Limit`] at `offset` if `value` exceeds `limit`.
+    fn check(&self, limit: Limit, value: usize, offset: usize) -> Result {
+        let max = match limit {
+            Limit::Depth => self.limits.depth,
+            Limit::Nodes => self.limits.nodes,
+            Limit::Entries => self.limits.entries,
+            Limit::StringLength => self.limits.string_length,
+            Limit::RawStringHashes => self.limits.raw_string_hashes,
+            Limit::NumberLength => self.limits.number_length,
+            Limit::BufferLength => self.limits.buffer_length,
+        };
+        if value > max {
+            return Err(Error::Limit {
+                limit,
+                max,
+                location: Some(Location::in_source(self.src, offset)),
+            });
+        }
+        Ok(())
+    }
+
+    /// Enter a children block, at its opening `{`.
+    fn open_children(&mut self) -> Result {
+        self.check(Limit::Depth, self.depth + 1, self.pos)?;
+        self.pos += 1;
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn describe_next(&self) -> String {
+        match self.rest().chars().next() {
+            Some(c) if is_newline(c) => "newline".into(),
+            Some(c) => format!("`{}`", c),
+            None => "end of input".into(),
+        }
+    }
+
+    /// Queue the comment from `start` to here, unless it is inside another.
+    /// A node commented out with a slashdash doesn't include its newline.
+    fn comment(&mut self, start: usize) {
+        if !self.skipping {
+            let text = self.src[start..self.pos].trim_end_matches(is_newline);
+            self.comments
+                .push_back(Span::new(start, start + text.len()));
+        }
+    }
+
+    /// Skip a `/* */` comment, which may nest.
+    fn skip_block_comment(&mut self) -> Result {
+        let start = self.pos;
+        let mut depth = 0usize;
+        loop {
+            if self.starts_with("/*") {
+                depth += 1;
+                self.pos += 2;
+            } else if self.starts_with("*/") {
+                depth -= 1;
+                self.pos += 2;
+                if depth == 0 {
+                    self.comment(start);
+                    return Ok(());
+                }
+            } else if let Some(c) = self.peek() {
+                self.bump(c);
+            } else {
+                return Err(self.unterminated("unterminated block comment", start));
+            }
+        }
+    }
+
+    /// Skip a `//` comment, stopping before the newline.
+    fn skip_line_comment(&mut self) {
+        let start = self.pos;
+        let len = self.rest().find(is_newline);
+        self.reached_end |= len.is_none();
+        self.pos += len.unwrap_or(self.rest().len());
+        self.comment(start);
+    }
+
+    fn eat_newline(&mut self) -> bool {
+        if self.starts_with("\r\n") {
+            self.pos += 2;
+            true
+        } else {
+            match self.peek() {
+                Some(c) if is_newline(c) => {
+                    self.bump(c);
+                    true
+                }
+                _ => false,
+            }
+        }
+    }
+
+    /// Skip whitespace and block comments; returns whether anything was skipped.
+    fn skip_whitespace(&mut self) -> Result<bool> {
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some(c) if is_whitespace(c) => self.bump(c),
+                Some('/') if self.starts_with("/*") => self.skip_block_comment()?,
+                _ => return Ok(self.pos != start),
+            }
+        }
+    }
+
+    /// Skip whitespace within a node, including line continuations.
+    fn skip_node_space(&mut self) -> Result<bool> {
+        let mut skipped = self.skip_whitespace()?;
+        while self.peek() == Some('\\') {
+            self.pos += 1;
+            self.skip_whitespace()?;
+            if self.starts_with("//") {
+                self.skip_line_comment();
+            }
+            if !self.eat_newline() && self.peek().is_some() {
+                return Err(self.error("expected newline after line continuation"));
+            }
+            skipped = true;
+            self.skip_whitespace()?;
+        }
+        Ok(skipped)
+    }
+
+    /// Skip whitespace, newlines and comments between nodes.
+    fn skip_line_space(&mut self) -> Result {
+        loop {
+            self.skip_whitespace()?;
+            if self.starts_with("//") {
+                self.skip_line_comment();
+            } else if !self.eat_newline() && !self.starts_with(";") {
+                return Ok(());
+            } else if self.starts_with(";") {
+                self.pos += 1;
+            }
+        }
+    }
+
+    fn bare_token(&mut self) -> &'de str {
+        let rest = self.rest();
+        let len = rest.find(|c| !is_identifier_char(c));
+        self.reached_end |= len.is_none();
+        let len = len.unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// If the input is at a raw string, returns the number of hashes and the prefix length.
+    fn raw_string_start(&mut self) -> Option<(usize, usize)> {
+        let rest = self.rest();
+        let (after_r, prefix) = match rest.strip_prefix('r') {
+            Some(after_r) => (after_r, 1),
+            None => (rest, 0),
+        };
+        let hashes = after_r.len() - after_r.trim_start_matches('#').len();
+        self.reached_end |= after_r.len() == hashes;
+        if after_r[hashes..].starts_with('"') && (prefix == 1 || hashes > 0) {
+            Some((hashes, prefix + hashes + 1))
+        } else {
+            None
+        }
+    }
+
+    fn parse_raw_string(&mut self, hashes: usize, prefix: usize) -> Result<Cow<'de, str>> {
+        let start = self.pos;
+        self.pos += prefix;
+        let body = self.rest();
+        let delimiter = format!("\"{}", "#".repeat(hashes));
+        match body.find(&delimiter) {
+            Some(close) => {
+                self.pos += close + delimiter.len();
+                Ok(Cow::Borrowed(&body[..close]))
+            }
+            None => Err(self.unterminated("unterminated raw string", start)),
+        }
+    }
+
+    fn parse_escaped_string(&mut self) -> Result<Cow<'de, str>> {
+        let start = self.pos;
+        self.pos += 1;
+        let body = self.rest();
+        let close = match find_unescaped(body, "\"") {
+            Some(close) => close,
+            None => return Err(self.unterminated("unterminated string", start)),
+        };
+        self.pos += close + 1;
+        unescape(&body[..close]).map_err(|(message, i)| self.error_at(message, start + 1 + i))
+    }
+
+    /// Parse a KDL v2 multi-line string, `"""` or `#"""` followed by a newline.
+    /// `prefix` is the length of the opening quotes, including any hashes.
+    fn parse_multi_line_string(
+        &mut self,
+        raw_hashes: Option<usize>,
+        prefix: usize,
+    ) -> Result<Cow<'de, str>> {
+        let start = self.pos;
+        self.pos += prefix;
+        let body = self.rest();
+        let (close, len) = match raw_hashes {
+            Some(hashes) => {
+                let delimiter = format!("\"\"\"{}", "#".repeat(hashes));
+                (body.find(&delimiter), delimiter.len())
+            }
+            None => (find_unescaped(body, "\"\"\""), 3),
+        };
+        let close = match close {
+            Some(close) => close,
+            None => return Err(self.unterminated("unterminated multi-line string", start)),
+        };
+        self.pos += close + len;
+        let text = dedent(&body[..close]).map_err(|message| self.error_at(message, start))?;
+        if raw_hashes.is_some() {
+            return Ok(Cow::Owned(text));
+        }
+        match unescape(&text) {
+            Ok(Cow::Borrowed(_)) => Ok(Cow::Owned(text)),
+            Ok(Cow::Owned(unescaped)) => Ok(Cow::Owned(unescaped)),
+            Err((message, _)) => Err(self.error_at(message, start)),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<Option<Cow<'de, str>>> {
+        let start = self.pos;
+        let s = self.parse_any_string()?;
+        if let Some(s) = &s {
+            self.check(Limit::StringLength, s.len(), start)?;
+        }
+        Ok(s)
+    }
+
+    /// Whether the `"` `skip` bytes before here is followed by the rest of
+    /// the `"""` and newline which open a multi-line string.
+    fn opens_multi_line(&mut self, skip: usize) -> bool {
+        self.looking_at(skip, "\"\"") && self.peek_at(skip + 2).is_some_and(is_newline)
+    }
+
+    fn parse_any_string(&mut self) -> Result<Option<Cow<'de, str>>> {
+        if let Some((hashes, prefix)) = self.raw_string_start() {
+            self.check(Limit::RawStringHashes, hashes, self.pos)?;
+            // only the v2 form without an `r` can open a multi-line raw string
+            if prefix == hashes + 1 && self.opens_multi_line(prefix) {
+                self.parse_multi_line_string(Some(hashes), prefix + 2)
+                    .map(Some)
+            } else {
+                self.parse_raw_string(hashes, prefix).map(Some)
+            }
+        } else if self.peek() == Some('"') && self.opens_multi_line(1) {
+            self.parse_multi_line_string(None, 3).map(Some)
+        } else if self.peek() == Some('"') {
+            self.parse_escaped_string().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Parse a node name, property name, or type annotation name.
+    fn parse_identifier(&mut self) -> Result<Cow<'de, str>> {
+        if let Some(s) = self.parse_string()? {
+            return Ok(s);
+        }
+        let start = self.pos;
+        let token = self.bare_token();
+        if token.is_empty() {
+            return Err(self.error(format!(
+                "expected identifier, found {}",
+                self.describe_next()
+            )));
+        }
+        if looks_like_number(token) || matches!(token, "true" | "false" | "null") {
+            return Err(self.error_at(
+                format!(
+                    "expected identifier, found `{}`; quote it to use it as a name",
+                    token
+                ),
+                start,
+            ));
+        }
+        self.check(Limit::StringLength, token.len(), start)?;
+        Ok(Cow::Borrowed(token))
+    }
+
+    fn parse_type_annotation(&mut self) -> Result<Option<Cow<'de, str>>> {
+        if self.peek() != Some('(') {
+            return Ok(None);
+        }
+        self.pos += 1;
+        self.skip_whitespace()?;
+        let ty = self.parse_identifier()?;
+        self.skip_whitespace()?;
+        if self.peek() != Some(')') {
+            return Err(self.error(format!(
+                "expected `)` to close type annotation, found {}",
+                self.describe_next()
+            )));
+        }
+        self.pos += 1;
+        Ok(Some(ty))
+    }
+
+    fn parse_value(&mut self) -> Result<Literal<'de>> {
+        if let Some(s) = self.parse_string()? {
+            return Ok(Literal::String(s));
+        }
+        let start = self.pos;
+        if self.peek() == Some('#') {
+            self.pos += 1;
+            return match self.bare_token() {
+                "true" => Ok(Literal::Bool(true)),
+                "false" => Ok(Literal::Bool(false)),
+                "null" => Ok(Literal::Null),
+                "inf" | "-inf" | "nan" => Ok(Literal::Number(&self.src[start..self.pos])),
+                _ => Err(self.error_at("unknown keyword", start)),
+            };
+        }
+        let token = self.bare_token();
+        match token {
+            "" => Err(self.error(format!("expected value, found {}", self.describe_next()))),
+            "true" => Ok(Literal::Bool(true)),
+            "false" => Ok(Literal::Bool(false)),
+            "null" => Ok(Literal::Null),
+            _ if looks_like_number(token) => {
+                self.check(Limit::NumberLength, token.len(), start)?;
+                if is_valid_number(token) {
+                    Ok(Literal::Number(token))
+                } else {
+                    Err(self.error_at(format!("invalid number `{}`", token), start))
+                }
+            }
+            // KDL v2 bare identifier strings
+            _ => {
+                self.check(Limit::StringLength, token.len(), start)?;
+                Ok(Literal::String(Cow::Borrowed(token)))
+            }
+        }
+    }
+
+    fn parse_entry(&mut self) -> Result<Event<'de>> {
+        let start = self.pos;
+        let ty = self.parse_type_annotation()?;
+        let value = self.parse_value()?;
+        if self.peek() != Some('=') {
+            return Ok(Event::Argument {
+                ty,
+                value,
+                span: Span::new(start, self.pos),
+            });
+        }
+        let name = match (ty, value) {
+            (None, Literal::String(name)) => name,
+            (Some(_), _) => {
+                return Err(self.error_at(
+                    "type annotations go on property values, not property names",
+                    start,
+                ))
+            }
+            (None, _) => return Err(self.error_at("expected property name", start)),
+        };
+        self.pos += 1;
+        let ty = self.parse_type_annotation()?;
+        let value = self.parse_value()?;
+        Ok(Event::Property {
+            name,
+            ty,
+            value,
+            span: Span::new(start, self.pos),
+        })
+    }
+
+    fn at_node_terminator(&mut self) -> bool {
+        match self.peek() {
+            None | Some(';') | Some('}') => true,
+            Some(c) if is_newline(c) => true,
+            _ => self.starts_with("//"),
+        }
+    }
+
+    /// Consume the node terminator; a closing `}` is left for the parent.
+    fn end_node(&mut self) -> Event<'de> {
+        let span = Span::new(self.pos, self.pos);
+        if self.peek() == Some(';') {
+            self.pos += 1;
+        } else if self.starts_with("//") {
+            self.skip_line_comment();
+        } else {
+            self.eat_newline();
+        }
+        self.state = State::Nodes;
+        Event::EndNode { span }
+    }
+
+    /// How far into the source the reader has read, in bytes.
+    pub(crate) fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// The nodes commented out with a slashdash so far, outside of any
+    /// other slashdash comment.
+    pub(crate) fn take_skipped_nodes(&mut self) -> Vec<Span> {
+        std::mem::take(&mut self.skipped_nodes)
+    }
+
+    /// Parse and discard a full node, for slashdash comments.
+    fn skip_node(&mut self) -> Result {
+        let start = self.pos;
+        let skipping = std::mem::replace(&mut self.skipping, true);
+        let mut depth = 0usize;
+        loop {
+            match self.next_event()? {
+                Some(Event::StartNode { .. }) => depth += 1,
+                Some(Event::EndNode { .. }) if depth == 1 => {
+                    self.skipping = skipping;
+                    return Ok(());
+                }
+                Some(Event::EndNode { .. }) => depth -= 1,
+                Some(_) if depth > 0 => {}
+                _ => return Err(self.error_at("expected node after `/-`", start)),
+            }
+        }
+    }
+
+    /// Parse and discard a children block, for slashdash comments.
+    fn skip_children(&mut self) -> Result {
+        self.open_children()?;
+        self.state = State::Nodes;
+        let entries = self.entries;
+        let skipping = std::mem::replace(&mut self.skipping, true);
+        let mut depth = 1usize;
+        while depth > 0 {
+            match self.next_event()? {
+                Some(Event::StartChildren { .. }) => depth += 1,
+                Some(Event::EndChildren { .. }) => depth -= 1,
+                Some(_) => {}
+                None => unreachable!("unclosed children block is reported by next_event"),
+            }
+        }
+        self.skipping = skipping;
+        self.entries = entries;
+        self.state = State::Entries;
+        Ok(())
+    }
+
+    /// The next comment skipped over, if any are waiting to be returned.
+    fn next_comment(&mut self) -> Option<Event<'de>> {
+        let span = self.comments.pop_front()?;
+        Some(Event::Comment {
+            text: &self.src[span.start..span.end],
+            span,
+        })
+    }
+
+    pub fn next_event(&mut self) -> Result<Option<Event<'de>>> {
+        loop {
+            if let Some(comment) = self.next_comment() {
+                return Ok(Some(comment));
+            }
+            match self.state {
+                State::Done => return Ok(None),
+                State::Nodes => {
+                    self.skip_line_space()?;
+                    if !self.comments.is_empty() {
+                        continue;
+                    }
+                    let start = self.pos;
+                    match self.peek() {
+                        None if self.depth > 0 => {
+                            return Err(self.error("unclosed children block; expected `}`"))
+                        }
+                        None => {
+                            self.state = State::Done;
+                            return Ok(None);
+                        }
+                        Some('}') if self.depth == 0 => {
+                            return Err(self.error("unexpected `}` outside of a children block"))
+                        }
+                        Some('}') => {
+                            self.pos += 1;
+                            self.depth -= 1;
+                            self.state = State::AfterChildren;
+                            return Ok(Some(Event::EndChildren {
+                                span: Span::new(start, self.pos),
+                            }));
+                        }
+                        Some('/') if self.starts_with("/-") => {
+                            let skipping = std::mem::replace(&mut self.skipping, true);
+                            // `/- /- node` comments out the same node; take every
+                            // `/-` here, rather than skipping each in another
+                            while self.starts_with("/-") {
+                                self.pos += 2;
+                                self.skip_line_space()?;
+                            }
+                            self.skip_node()?;
+                            self.skipping = skipping;
+                            if !self.skipping {
+                                self.skipped_nodes.push(Span::new(start, self.pos));
+                            }
+                            self.comment(start);
+                        }
+                        Some(_) => {
+                            self.check(Limit::Nodes, self.nodes + 1, start)?;
+                            self.nodes += 1;
+                            self.entries = 0;
+                            let ty = self.parse_type_annotation()?;
+                            let name = self.parse_identifier()?;
+                            self.state = State::Entries;
+                            self.spaced = false;
+                            return Ok(Some(Event::StartNode {
+                                ty,
+                                name,
+                                span: Span::new(start, self.pos),
+                            }));
+                        }
+                    }
+                }
+                State::Entries => {
+                    self.spaced |= self.skip_node_space()?;
+                    if !self.comments.is_empty() {
+                        continue;
+                    }
+                    if self.at_node_terminator() {
+                        return Ok(Some(self.end_node()));
+                    }
+                    let start = self.pos;
+                    if self.peek() == Some('{') {
+                        self.open_children()?;
+                        self.state = State::Nodes;
+                        return Ok(Some(Event::StartChildren {
+                            span: Span::new(start, self.pos),
+                        }));
+                    }
+                    if !self.spaced {
+                        return Err(self.error(format!(
+                            "expected whitespace before node entry, found {}",
+                            self.describe_next()
+                        )));
+                    }
+                    if self.starts_with("/-") {
+                        self.pos += 2;
+                        let skipping = std::mem::replace(&mut self.skipping, true);
+                        self.skip_node_space()?;
+                        if self.peek() == Some('{') {
+                            self.skip_children()?;
+                        } else {
+                            self.parse_entry()?;
+                        }
+                        self.skipping = skipping;
+                        self.comment(start);
+                        self.spaced = false;
+                        continue;
+                    }
+                    self.check(Limit::Entries, self.entries + 1, start)?;
+                    self.entries += 1;
+                    let entry = self.parse_entry()?;
+                    self.spaced = false;
+                    return Ok(Some(entry));
+                }
+                State::AfterChildren => {
+                    self.skip_node_space()?;
+                    if !self.comments.is_empty() {
+                        continue;
+                    }
+                    if self.starts_with("/-") {
+                        let start = self.pos;
+                        self.pos += 2;
+                        let skipping = std::mem::replace(&mut self.skipping, true);
+                        self.skip_node_space()?;
+                        if self.peek() == Some('{') {
+                            self.skip_children()?;
+                            self.skipping = skipping;
+                            self.comment(start);
+                            self.state = State::AfterChildren;
+                            continue;
+                        }
+                        return Err(self.error("expected children block after `/-`"));
+                    }
+                    if !self.at_node_terminator() {
+                        return Err(self.error(format!(
+                            "expected newline or `;` after children block, found {}",
+                            self.describe_next()
+                        )));
+                    }
+                    return Ok(Some(self.end_node()));
+                }
+            }
+        }
+    }
+}
+
+impl<'de> Iterator for Reader<'de> {
+    type Item = Result<Event<'de>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let event = self.next_event().transpose();
+        if let Some(Err(_)) = event {
+            self.state = State::Done;
+            self.comments.clear();
+        }
+        event
+    }
+}
@@ -1,8 +1,13 @@
 use {
-    crate::*,
+    crate::{
+        error::Limit,
+        read::{is_newline, is_whitespace},
+        *,
+    },
     paste::paste,
     serde::ser::*,
     std::{
+        borrow::Cow,
         fmt::{self, Write as _},
         io::{self, prelude::*},
         marker::PhantomData,
@@ -35,7 +40,12 @@ fn count_needed_hashes(s: &str) -> usize {
 }
 
 fn is_valid_kdl_identifier(s: &str) -> bool {
-    !s.starts_with(&['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'][..])
+    let unsigned = s.strip_prefix(&['+', '-'][..]).unwrap_or(s);
+    let undotted = unsigned.strip_prefix('.').unwrap_or(unsigned);
+    !s.is_empty()
+        && !undotted.starts_with(&['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'][..])
+        && !matches!(s, "true" | "false" | "null" | "inf" | "-inf" | "nan")
+        && !s.starts_with('#')
         && !s.contains("//")
         && !s.contains(|c: char| {
             c as u32 <= 0x20
@@ -45,12 +55,130 @@ fn is_valid_kdl_identifier(s: &str) -> bool {
         })
 }
 
+/// Displays a string as a KDL string in `version`: bare if it is an
+/// identifier which may be, raw if that avoids escapes, and otherwise quoted
+/// with escapes.
+struct Quoted<'a> {
+    s: &'a str,
+    version: Version,
+    bare: bool,
+}
+
+impl<'a> Quoted<'a> {
+    /// A node name, property name, or type annotation.
+    fn identifier(s: &'a str, version: Version) -> Self {
+        Quoted {
+            s,
+            version,
+            bare: true,
+        }
+    }
+
+    /// A string value.
+    fn string(s: &'a str, version: Version) -> Self {
+        Quoted {
+            s,
+            version,
+            bare: false,
+        }
+    }
+}
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = self.s;
+        if self.bare && is_valid_kdl_identifier(s) {
+            return f.write_str(s);
+        }
+        match self.version {
+            // keep the output on a single line
+            Version::V1 if !s.contains(is_newline) => {
+                let hashes = &HASHES_LITERAL[..count_needed_hashes(s)];
+                write!(f, r#"r{hashes}"{}"{hashes}"#, s, hashes = hashes)
+            }
+            Version::V2
+                if s.contains(['"', '\\'])
+                    && !s.contains(|c: char| c.is_control() || is_newline(c)) =>
+            {
+                let hashes = &HASHES_LITERAL[..count_needed_hashes(s).max(1)];
+                write!(f, r#"{hashes}"{}"{hashes}"#, s, hashes = hashes)
+            }
+            _ => {
+                f.write_char('"')?;
+                for c in s.chars() {
+                    match c {
+                        '"' => f.write_str(r#"\""#)?,
+                        '\\' => f.write_str(r"\\")?,
+                        '\n' => f.write_str(r"\n")?,
+                        '\r' => f.write_str(r"\r")?,
+                        '\t' => f.write_str(r"\t")?,
+                        '\u{08}' => f.write_str(r"\b")?,
+                        '\u{0C}' => f.write_str(r"\f")?,
+                        c if c.is_control() || is_newline(c) => write!(f, r"\u{{{:x}}}", c as u32)?,
+                        c => f.write_char(c)?,
+                    }
+                }
+                f.write_char('"')
+            }
+        }
+    }
+}
+
+/// Displays a keyword, `true`, `false`, `null`, `inf`, `-inf`, or `nan`, as
+/// it is spelled in `version`.
+struct Keyword(Version, &'static str);
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == Version::V2 {
+            f.write_char('#')?;
+        }
+        f.write_str(self.1)
+    }
+}
+
+/// Displays a float, with the non-finite values as keywords.
+struct Float<T>(Version, T);
+
+impl<T: Into<f64> + fmt::Display + Copy> fmt::Display for Float<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v: f64 = self.1.into();
+        if v.is_nan() {
+            Keyword(self.0, "nan").fmt(f)
+        } else if v.is_infinite() {
+            Keyword(self.0, if v > 0.0 { "inf" } else { "-inf" }).fmt(f)
+        } else {
+            self.1.fmt(f)
+        }
+    }
+}
+
+/// Whether a string can be written as a multi-line string without escapes
+/// changing its meaning: every line break is a `\n`, no other control
+/// characters need escaping, and no line consists only of whitespace, as
+/// those lines are read back as empty.
+fn fits_multi_line_string(s: &str) -> bool {
+    s.contains('\n')
+        && !s.contains(|c: char| c != '\n' && (is_newline(c) || c.is_control() && c != '\t'))
+        && s.split('\n')
+            .all(|line| line.is_empty() || !line.chars().all(is_whitespace))
+}
+
 pub trait Format {
     type Sink: ?Sized;
 
     // Type annotation
     fn provide_type_annotation(&mut self, s: &mut Self::Sink, ty: &'static str) -> io::Result<()>;
     fn require_type_annotation(&mut self, s: &mut Self::Sink, ty: &'static str) -> io::Result<()>;
+    /// Like [`require_type_annotation`](Format::require_type_annotation), for a
+    /// type annotation which is only known at runtime.
+    fn require_dynamic_type_annotation(&mut self, s: &mut Self::Sink, ty: &str) -> io::Result<()> {
+        let _ = (s, ty);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "dynamic type annotations are not supported by this formatter",
+        ))
+    }
 
     // Primitives
     fn write_bool(&mut self, s: &mut Self::Sink, v: bool) -> io::Result<()>;
@@ -69,12 +197,46 @@ pub trait Format {
     fn write_unit(&mut self, s: &mut Self::Sink) -> io::Result<()>;
     fn write_string(&mut self, s: &mut Self::Sink, v: &str) -> io::Result<()>;
     fn write_bytes(&mut self, s: &mut Self::Sink, v: &[u8]) -> io::Result<()>;
+    /// Write a number given as it is written in KDL.
+    fn write_number(&mut self, s: &mut Self::Sink, v: &str) -> io::Result<()> {
+        let _ = (s, v);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "numbers in KDL syntax are not supported by this formatter",
+        ))
+    }
 
     // Struct, Tuple, Seq
     fn begin_group(&mut self, s: &mut Self::Sink) -> io::Result<()>;
     fn end_group(&mut self, s: &mut Self::Sink) -> io::Result<()>;
     fn begin_field(&mut self, s: &mut Self::Sink, name: Option<&'static str>) -> io::Result<()>;
     fn end_field(&mut self, s: &mut Self::Sink) -> io::Result<()>;
+    /// Request where the next value is placed in its node. Formatters which
+    /// don't choose between KDL Values and child nodes may ignore this.
+    fn set_placement(&mut self, s: &mut Self::Sink, placement: Placement) -> io::Result<()> {
+        let _ = (s, placement);
+        Ok(())
+    }
+    /// Request how the next value is written, if it is an integer.
+    /// Formatters which only write decimal may ignore this.
+    fn set_number_style(&mut self, s: &mut Self::Sink, style: NumberStyle) -> io::Result<()> {
+        let _ = (s, style);
+        Ok(())
+    }
+    /// Request which KDL version to write. Formatters which only write one
+    /// may ignore this.
+    fn set_version(&mut self, version: Version) {
+        let _ = version;
+    }
+    /// Whether a leaf value placed with [`Placement::Argument`] next would
+    /// be written as a KDL Argument.
+    fn can_place_argument(&self) -> bool {
+        false
+    }
+    /// Whether the next value already has a mandatory type annotation.
+    fn has_type_annotation(&self) -> bool {
+        false
+    }
 
     // Maps
     fn begin_map(&mut self, s: &mut Self::Sink) -> io::Result<()>;
@@ -83,6 +245,19 @@ pub trait Format {
     fn begin_map_value(&mut self, s: &mut Self::Sink) -> io::Result<()>;
     fn end_map_value(&mut self, s: &mut Self::Sink) -> io::Result<()>;
     fn end_map(&mut self, s: &mut Self::Sink) -> io::Result<()>;
+    /// Begin a map entry with a string key, as a node named by the key where
+    /// the formatter supports it, and begin its value.
+    fn begin_named_map_value(&mut self, s: &mut Self::Sink, key: &str) -> io::Result<()> {
+        self.begin_map_key(s)?;
+        self.write_string(s, key)?;
+        self.end_map_key(s)?;
+        self.begin_map_value(s)
+    }
+    /// End a map entry begun with
+    /// [`begin_named_map_value`](Format::begin_named_map_value).
+    fn end_named_map_value(&mut self, s: &mut Self::Sink) -> io::Result<()> {
+        self.end_map_value(s)
+    }
 }
 
 /// A formatter for SiK that prioritizes single-pass zero-copy serialization.
@@ -94,8 +269,9 @@ pub trait Format {
 /// single-line, unformatted SiK for simplicity.
 #[derive(Debug)]
 pub struct SimpleFormatter<W: ?Sized> {
-    ty: Option<&'static str>,
-    field: Option<&'static str>,
+    ty: Option<Cow<'static, str>>,
+    field: Option<Cow<'static, str>>,
+    version: Version,
     _sink: PhantomData<W>,
 }
 
@@ -103,7 +279,8 @@ impl<W: ?Sized> Default for SimpleFormatter<W> {
     fn default() -> Self {
         Self {
             ty: None,
-            field: Some("-"),
+            field: Some(Cow::Borrowed("-")),
+            version: Version::V1,
             _sink: PhantomData,
         }
     }
@@ -119,24 +296,10 @@ impl<W: ?Sized> SimpleFormatter<W> {
         W: io::Write,
     {
         if let Some(ty) = self.ty.take() {
-            assert!(
-                is_valid_kdl_identifier(ty),
-                "Provided an invalid KDL identifier as type annotation; this is a bug in serde-kdl"
-            );
-            write!(w, "({})", ty)?;
+            write!(w, "({})", Quoted::identifier(&ty, self.version))?;
         }
         if let Some(field) = self.field.take() {
-            if is_valid_kdl_identifier(field) {
-                write!(w, "{} ", field)?;
-            } else {
-                let hash_count = count_needed_hashes(field);
-                write!(
-                    w,
-                    r#"r{hashes}"{}"{hashes} "#,
-                    field,
-                    hashes = &HASHES_LITERAL[..hash_count]
-                )?;
-            }
+            write!(w, "{} ", Quoted::identifier(&field, self.version))?;
         } else {
             unreachable!("all values should be written in a field in SimpleFormatter")
         }
@@ -155,18 +318,6 @@ macro_rules! forward_write_to_display {
             }
         )*}
     };
-
-    ([$map_err:ident] $($T:ident),* $(,)?) => {
-        paste! {$(
-            fn [<write_ $T:snake>](&mut self, s: &mut Self::Sink, v: $T) -> io::Result<()>
-            {
-                self.provide_type_annotation(s, stringify!($T))?;
-                self.write_pre_simple_value(s)?;
-                write!(s, "{}", v).map_err($map_err)?;
-                Ok(())
-            }
-        )*}
-    };
 }
 
 impl<W: ?Sized> Format for SimpleFormatter<W>
@@ -188,29 +339,52 @@ where
         if self.ty.is_some() {
             panic!("Provided two mandatory type annotations (this is a bug in serde-kdl)");
         }
-        self.ty = Some(ty);
+        self.ty = Some(Cow::Borrowed(ty));
+        Ok(())
+    }
+
+    fn require_dynamic_type_annotation(&mut self, _s: &mut Self::Sink, ty: &str) -> io::Result<()> {
+        if self.ty.is_some() {
+            panic!("Provided two mandatory type annotations (this is a bug in serde-kdl)");
+        }
+        self.ty = Some(Cow::Owned(ty.to_owned()));
         Ok(())
     }
 
+    fn write_bool(&mut self, s: &mut Self::Sink, v: bool) -> io::Result<()> {
+        self.provide_type_annotation(s, "bool")?;
+        self.write_pre_value(s)?;
+        write!(
+            s,
+            "{}",
+            Keyword(self.version, if v { "true" } else { "false" })
+        )
+    }
+
     forward_write_to_display! {
-        bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64,
+        u8, u16, u32, u64, u128, i8, i16, i32, i64, i128,
+    }
+
+    fn write_f32(&mut self, s: &mut Self::Sink, v: f32) -> io::Result<()> {
+        self.provide_type_annotation(s, "f32")?;
+        self.write_pre_value(s)?;
+        write!(s, "{}", Float(self.version, v))
+    }
+
+    fn write_f64(&mut self, s: &mut Self::Sink, v: f64) -> io::Result<()> {
+        self.provide_type_annotation(s, "f64")?;
+        self.write_pre_value(s)?;
+        write!(s, "{}", Float(self.version, v))
     }
 
     fn write_unit(&mut self, s: &mut Self::Sink) -> io::Result<()> {
         self.write_pre_value(s)?;
-        write!(s, "null")
+        write!(s, "{}", Keyword(self.version, "null"))
     }
 
     fn write_string(&mut self, s: &mut Self::Sink, v: &str) -> io::Result<()> {
         self.write_pre_value(s)?;
-        let hash_count = count_needed_hashes(v);
-
-        write!(
-            s,
-            r#"r{hashes}"{}"{hashes}"#,
-            v,
-            hashes = &HASHES_LITERAL[..hash_count]
-        )
+        write!(s, "{}", Quoted::string(v, self.version))
     }
 
     fn write_bytes(&mut self, s: &mut Self::Sink, v: &[u8]) -> io::Result<()> {
@@ -224,6 +398,11 @@ where
         write!(s, r#"""#)
     }
 
+    fn write_number(&mut self, s: &mut Self::Sink, v: &str) -> io::Result<()> {
+        self.write_pre_value(s)?;
+        write!(s, "{}", v)
+    }
+
     fn begin_group(&mut self, s: &mut Self::Sink) -> io::Result<()> {
         self.write_pre_value(s)?;
         write!(s, "{{ ")
@@ -234,10 +413,18 @@ where
     }
 
     fn begin_field(&mut self, _s: &mut Self::Sink, name: Option<&'static str>) -> io::Result<()> {
-        self.field = Some(name.unwrap_or("-"));
+        self.field = Some(Cow::Borrowed(name.unwrap_or("-")));
         Ok(())
     }
 
+    fn has_type_annotation(&self) -> bool {
+        self.ty.is_some()
+    }
+
+    fn set_version(&mut self, version: Version) {
+        self.version = version;
+    }
+
     fn end_field(&mut self, s: &mut Self::Sink) -> io::Result<()> {
         write!(s, "; ")
     }
@@ -269,30 +456,71 @@ where
     fn end_map(&mut self, s: &mut Self::Sink) -> io::Result<()> {
         self.end_group(s)
     }
+
+    fn begin_named_map_value(&mut self, _: &mut Self::Sink, key: &str) -> io::Result<()> {
+        self.field = Some(Cow::Owned(key.to_owned()));
+        Ok(())
+    }
+
+    fn end_named_map_value(&mut self, s: &mut Self::Sink) -> io::Result<()> {
+        self.end_field(s)
+    }
 }
 
 /// A formatter for SiK that prioritizes human-friendly serialization.
 ///
+/// When writing KDL v2, strings with line breaks are written as multi-line
+/// strings, indented to match their node.
+///
 /// This includes a significant amount of backtracing to retroactively
 /// choose the optimal format, so this formatter only supports writing
 /// to in-memory buffers (i.e. `String`s), and not arbitrary IO objects.
 #[derive(Debug)]
 pub struct HumanFormatter {
     root: bool,
+    root_start: usize,
+    root_nodes: usize,
+    root_is_literal: bool,
     indent: u8,
     in_inline_group: bool,
-    ty: Option<&'static str>,
-    field: Option<&'static str>,
+    /// Whether nothing has been written yet for the innermost group.
+    group_is_empty: bool,
+    /// Whether the innermost group is written without a children block
+    /// when empty, as it is a node with a layout of its own.
+    bare_if_empty: bool,
+    /// Whether a property has been written for the innermost inline group.
+    wrote_property: bool,
+    /// For each open children block, where its node's header ends, and the
+    /// entries written since it opened, to insert there when it closes.
+    headers: Vec<(usize, String)>,
+    /// The start of an entry to move to its node's header at `end_field`.
+    moving_entry: Option<usize>,
+    placement: Option<Placement>,
+    number_style: Option<NumberStyle>,
+    ty: Option<Cow<'static, str>>,
+    field: Option<Cow<'static, str>>,
+    version: Version,
 }
 
 impl Default for HumanFormatter {
     fn default() -> Self {
         Self {
             root: true,
+            root_start: 0,
+            root_nodes: 0,
+            root_is_literal: false,
             indent: 1,
             in_inline_group: false,
+            group_is_empty: false,
+            bare_if_empty: false,
+            wrote_property: false,
+            headers: Vec::new(),
+            moving_entry: None,
+            placement: None,
+            number_style: None,
             ty: None,
-            field: Some("-"),
+            field: Some(Cow::Borrowed("-")),
+            version: Version::V1,
         }
     }
 }
@@ -302,39 +530,78 @@ fn as_io(_: fmt::Error) -> io::ErrorKind {
 }
 
 impl HumanFormatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A formatter for a single `-` node, rather than a whole document, as
+    /// when appending values to a stream. Compound values are written as
+    /// the node's entries and children instead of as the document's nodes.
+    pub fn node() -> Self {
+        Self {
+            root: false,
+            ..Self::default()
+        }
+    }
+
+    fn open_block(&mut self, s: &mut String) -> io::Result<()> {
+        self.headers.push((s.len(), String::new()));
+        self.indent += 4;
+        write!(s, " {{").map_err(as_io)?;
+        self.in_inline_group = false;
+        Ok(())
+    }
+
     fn write_pre_simple_value(&mut self, s: &mut String) -> io::Result<()> {
-        if self.in_inline_group {
+        self.group_is_empty = false;
+        self.number_style = None;
+        let placement = self.placement.take();
+        if placement == Some(Placement::Child) && self.in_inline_group {
+            self.open_block(s)?;
+        }
+        if placement == Some(Placement::Argument) && self.in_inline_group && !self.wrote_property {
+            self.field = None;
+        }
+        // a property, or an argument of a flattened `Document`, after the
+        // children block has opened
+        let moving_entry = !self.in_inline_group
+            && !self.headers.is_empty()
+            && match placement {
+                Some(Placement::Property) => self.field.is_some(),
+                Some(Placement::Argument) => self.field.is_none(),
+                _ => false,
+            };
+        if moving_entry {
+            self.moving_entry = Some(s.len());
+        }
+        let inline = self.in_inline_group || moving_entry;
+        if inline {
             write!(s, " ").map_err(as_io)?;
         } else if !self.root {
+            self.note_root_node(s, self.field.is_none());
             write!(s, "{}", &INDENT_LITERAL[..self.indent as _]).map_err(as_io)?;
         }
-        if let Some(ty) = self.ty.take() {
-            assert!(
-                is_valid_kdl_identifier(ty),
-                "Provided an invalid KDL identifier as type annotation; this is a bug in serde-kdl"
-            );
-            write!(s, "({})", ty).map_err(as_io)?;
-        }
+        let ty = self.ty.take();
         let mut wrote_field_name = false;
+        if !inline {
+            // type annotation on the node
+            if let Some(ty) = &ty {
+                write!(s, "({})", Quoted::identifier(ty, self.version)).map_err(as_io)?;
+            }
+        }
         if let Some(field) = self.field.take() {
             wrote_field_name = true;
-            if is_valid_kdl_identifier(field) {
-                write!(s, "{}", field).map_err(as_io)?;
-            } else {
-                let hash_count = count_needed_hashes(field);
-                write!(
-                    s,
-                    r#"r{hashes}"{}"{hashes}"#,
-                    field,
-                    hashes = &HASHES_LITERAL[..hash_count]
-                )
-                .map_err(as_io)?;
-            }
+            write!(s, "{}", Quoted::identifier(&field, self.version)).map_err(as_io)?;
         }
-        if self.in_inline_group {
+        if inline {
             if wrote_field_name {
+                self.wrote_property = true;
                 write!(s, "=").map_err(as_io)?;
             }
+            // type annotation on the argument/property value
+            if let Some(ty) = &ty {
+                write!(s, "({})", Quoted::identifier(ty, self.version)).map_err(as_io)?;
+            }
         } else {
             if !wrote_field_name {
                 // anonymous node name
@@ -346,38 +613,128 @@ impl HumanFormatter {
     }
 
     fn write_pre_compound_value(&mut self, s: &mut String) -> io::Result<()> {
+        self.group_is_empty = false;
+        // compound values are always child nodes
+        self.placement = None;
+        self.number_style = None;
         if self.in_inline_group {
-            self.indent += 4;
-            write!(s, " {{{}", &INDENT_LITERAL[..self.indent as _]).map_err(as_io)?;
-            self.in_inline_group = false;
+            self.open_block(s)?;
+            write!(s, "{}", &INDENT_LITERAL[..self.indent as _]).map_err(as_io)?;
         } else if !self.root {
+            self.note_root_node(s, self.field.is_none());
             write!(s, "{}", &INDENT_LITERAL[..self.indent as _]).map_err(as_io)?;
         }
         if let Some(ty) = self.ty.take() {
-            assert!(
-                is_valid_kdl_identifier(ty),
-                "Provided an invalid KDL identifier as type annotation; this is a bug in serde-kdl"
-            );
-            write!(s, "({})", ty).map_err(as_io)?;
+            write!(s, "({})", Quoted::identifier(&ty, self.version)).map_err(as_io)?;
         }
         if let Some(field) = self.field.take() {
-            if is_valid_kdl_identifier(field) {
-                write!(s, "{}", field).map_err(as_io)?;
-            } else {
-                let hash_count = count_needed_hashes(field);
-                write!(
-                    s,
-                    r#"r{hashes}"{}"{hashes}"#,
-                    field,
-                    hashes = &HASHES_LITERAL[..hash_count]
-                )
-                .map_err(as_io)?;
-            }
+            write!(s, "{}", Quoted::identifier(&field, self.version)).map_err(as_io)?;
         } else {
             write!(s, "-").map_err(as_io)?;
         }
         Ok(())
     }
+
+    /// Write a multi-line string, with its lines and closing quotes indented
+    /// one level deeper than the node it belongs to.
+    fn write_multi_line_string(&self, s: &mut String, v: &str) {
+        let hashes = if v.contains('\\') || v.contains(r#"""""#) {
+            &HASHES_LITERAL[..count_needed_hashes(v).max(1)]
+        } else {
+            ""
+        };
+        let indent = &INDENT_LITERAL[1..self.indent as usize + 4];
+        s.push_str(hashes);
+        s.push_str(r#"""""#);
+        for line in v.split('\n') {
+            s.push('\n');
+            if !line.is_empty() {
+                s.push_str(indent);
+                s.push_str(line);
+            }
+        }
+        s.push('\n');
+        s.push_str(indent);
+        s.push_str(r#"""""#);
+        s.push_str(hashes);
+    }
+
+    /// Track the nodes written at the document root.
+    fn note_root_node(&mut self, s: &str, literal: bool) {
+        if self.indent == 1 {
+            if self.root_nodes == 0 {
+                self.root_start = s.len();
+            }
+            self.root_nodes += 1;
+            self.root_is_literal = literal;
+        }
+    }
+
+    /// A document that is a single `-` node reads as the root value itself,
+    /// so if an implied root only contains one such node, it needs to be
+    /// wrapped in an explicit root node.
+    fn disambiguate_implied_root(&self, s: &mut String) {
+        if self.root_nodes == 1 && self.root_is_literal {
+            let nodes = s.split_off(self.root_start);
+            s.push_str("\n- {");
+            s.push_str(&nodes.replace('\n', "\n    "));
+            s.push_str("\n}");
+        }
+    }
+}
+
+/// Write an integer, given as its sign and magnitude, in `style`.
+fn write_integer(
+    s: &mut String,
+    negative: bool,
+    magnitude: u128,
+    style: NumberStyle,
+) -> fmt::Result {
+    if negative {
+        s.push('-');
+    }
+    match style {
+        NumberStyle::Decimal => write!(s, "{}", magnitude),
+        NumberStyle::Grouped => {
+            let digits = magnitude.to_string();
+            // one to three leading digits, then groups of three
+            let (head, mut rest) = digits.split_at((digits.len() - 1) % 3 + 1);
+            s.push_str(head);
+            while !rest.is_empty() {
+                let (group, tail) = rest.split_at(3);
+                s.push('_');
+                s.push_str(group);
+                rest = tail;
+            }
+            Ok(())
+        }
+        NumberStyle::Hex => write!(s, "0x{:X}", magnitude),
+        NumberStyle::Octal => write!(s, "0o{:o}", magnitude),
+        NumberStyle::Binary => write!(s, "0b{:b}", magnitude),
+    }
+}
+
+macro_rules! write_integer {
+    (signed: $($I:ident),*; unsigned: $($U:ident),*;) => {
+        paste! {$(
+            fn [<write_ $I>](&mut self, s: &mut Self::Sink, v: $I) -> io::Result<()> {
+                let style = self.number_style.take().unwrap_or(NumberStyle::Decimal);
+                self.provide_type_annotation(s, stringify!($I))?;
+                self.write_pre_simple_value(s)?;
+                write_integer(s, v < 0, v.unsigned_abs().into(), style).map_err(as_io)?;
+                Ok(())
+            }
+        )*}
+        paste! {$(
+            fn [<write_ $U>](&mut self, s: &mut Self::Sink, v: $U) -> io::Result<()> {
+                let style = self.number_style.take().unwrap_or(NumberStyle::Decimal);
+                self.provide_type_annotation(s, stringify!($U))?;
+                self.write_pre_simple_value(s)?;
+                write_integer(s, false, v.into(), style).map_err(as_io)?;
+                Ok(())
+            }
+        )*}
+    };
 }
 
 impl Format for HumanFormatter {
@@ -392,31 +749,58 @@ impl Format for HumanFormatter {
         if self.ty.is_some() {
             panic!("Provided two mandatory type annotations (this is a bug in serde-kdl)");
         }
-        self.ty = Some(ty);
+        self.ty = Some(Cow::Borrowed(ty));
         Ok(())
     }
 
-    forward_write_to_display! { [as_io]
-        bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64,
+    fn require_dynamic_type_annotation(&mut self, _: &mut Self::Sink, ty: &str) -> io::Result<()> {
+        if self.ty.is_some() {
+            panic!("Provided two mandatory type annotations (this is a bug in serde-kdl)");
+        }
+        self.ty = Some(Cow::Owned(ty.to_owned()));
+        Ok(())
+    }
+
+    fn write_bool(&mut self, s: &mut Self::Sink, v: bool) -> io::Result<()> {
+        self.provide_type_annotation(s, "bool")?;
+        self.write_pre_simple_value(s)?;
+        let v = Keyword(self.version, if v { "true" } else { "false" });
+        write!(s, "{}", v).map_err(as_io)?;
+        Ok(())
+    }
+
+    fn write_f32(&mut self, s: &mut Self::Sink, v: f32) -> io::Result<()> {
+        self.provide_type_annotation(s, "f32")?;
+        self.write_pre_simple_value(s)?;
+        write!(s, "{}", Float(self.version, v)).map_err(as_io)?;
+        Ok(())
+    }
+
+    fn write_f64(&mut self, s: &mut Self::Sink, v: f64) -> io::Result<()> {
+        self.provide_type_annotation(s, "f64")?;
+        self.write_pre_simple_value(s)?;
+        write!(s, "{}", Float(self.version, v)).map_err(as_io)?;
+        Ok(())
+    }
+
+    write_integer! {
+        signed: i8, i16, i32, i64, i128;
+        unsigned: u8, u16, u32, u64, u128;
     }
 
     fn write_unit(&mut self, s: &mut Self::Sink) -> io::Result<()> {
         self.write_pre_simple_value(s)?;
-        write!(s, "null").map_err(as_io)?;
+        write!(s, "{}", Keyword(self.version, "null")).map_err(as_io)?;
         Ok(())
     }
 
     fn write_string(&mut self, s: &mut Self::Sink, v: &str) -> io::Result<()> {
         self.write_pre_simple_value(s)?;
-        let hash_count = count_needed_hashes(v);
-
-        write!(
-            s,
-            r#"r{hashes}"{}"{hashes}"#,
-            v,
-            hashes = &HASHES_LITERAL[..hash_count]
-        )
-        .map_err(as_io)?;
+        if self.version == Version::V2 && fits_multi_line_string(v) {
+            self.write_multi_line_string(s, v);
+        } else {
+            write!(s, "{}", Quoted::string(v, self.version)).map_err(as_io)?;
+        }
         Ok(())
     }
 
@@ -428,49 +812,99 @@ impl Format for HumanFormatter {
         Ok(())
     }
 
+    fn write_number(&mut self, s: &mut Self::Sink, v: &str) -> io::Result<()> {
+        self.write_pre_simple_value(s)?;
+        s.push_str(v);
+        Ok(())
+    }
+
     // We put as many simple node arguments/properties as possible before the block.
     // But also, once we've opened the block, further fields are just in the block.
     // This is simple, requires 0 rewriting, and generates reasonable output.
 
     fn begin_group(&mut self, s: &mut Self::Sink) -> io::Result<()> {
+        self.bare_if_empty = self.placement == Some(Placement::Node);
         if self.root {
             // first level is just root nodes
             // but only if no root type annotation
             if self.ty.is_none() {
                 self.field = None;
+                self.placement = None;
                 self.root = false;
                 return Ok(());
             }
+            self.write_pre_compound_value(s)?;
+            self.root = false;
+        } else {
+            self.write_pre_compound_value(s)?;
         }
-        self.write_pre_compound_value(s)?;
         self.in_inline_group = true;
+        self.group_is_empty = true;
+        self.wrote_property = false;
         Ok(())
     }
 
     fn end_group(&mut self, s: &mut Self::Sink) -> io::Result<()> {
         if self.in_inline_group {
+            if self.group_is_empty && !self.bare_if_empty {
+                // an empty children block, so it doesn't read as unit/none
+                write!(s, " {{}}").map_err(as_io)?;
+                self.group_is_empty = false;
+            }
             self.in_inline_group = false;
             return Ok(());
         }
         if self.indent < 4 {
             // first level is just root nodes
+            self.disambiguate_implied_root(s);
             write!(s, "{}", &INDENT_LITERAL[..self.indent as _]).map_err(as_io)?;
             return Ok(());
         }
         self.indent -= 4;
+        let (header, entries) = self.headers.pop().unwrap();
+        if !entries.is_empty() {
+            s.insert_str(header, &entries);
+        }
         write!(s, "{}}}", &INDENT_LITERAL[..self.indent as _]).map_err(as_io)?;
         Ok(())
     }
 
     fn begin_field(&mut self, _: &mut Self::Sink, name: Option<&'static str>) -> io::Result<()> {
-        self.field = name;
+        self.field = name.map(Cow::Borrowed);
+        Ok(())
+    }
+
+    fn end_field(&mut self, s: &mut Self::Sink) -> io::Result<()> {
+        if let Some(start) = self.moving_entry.take() {
+            let (_, entries) = self.headers.last_mut().unwrap();
+            entries.push_str(&s[start..]);
+            s.truncate(start);
+        }
         Ok(())
     }
 
-    fn end_field(&mut self, _: &mut Self::Sink) -> io::Result<()> {
+    fn set_placement(&mut self, _: &mut Self::Sink, placement: Placement) -> io::Result<()> {
+        self.placement = Some(placement);
         Ok(())
     }
 
+    fn set_number_style(&mut self, _: &mut Self::Sink, style: NumberStyle) -> io::Result<()> {
+        self.number_style = Some(style);
+        Ok(())
+    }
+
+    fn set_version(&mut self, version: Version) {
+        self.version = version;
+    }
+
+    fn can_place_argument(&self) -> bool {
+        self.in_inline_group && !self.wrote_property
+    }
+
+    fn has_type_annotation(&self) -> bool {
+        self.ty.is_some()
+    }
+
     // No cleverness yet; just use `{ - { key {}; value {}; } }` repr for now
 
     fn begin_map(&mut self, s: &mut Self::Sink) -> io::Result<()> {
@@ -500,8 +934,82 @@ impl Format for HumanFormatter {
     fn end_map(&mut self, s: &mut Self::Sink) -> io::Result<()> {
         self.end_group(s)
     }
+
+    fn begin_named_map_value(&mut self, _: &mut Self::Sink, key: &str) -> io::Result<()> {
+        self.field = Some(Cow::Owned(key.to_owned()));
+        Ok(())
+    }
+
+    fn end_named_map_value(&mut self, s: &mut Self::Sink) -> io::Result<()> {
+        self.end_field(s)
+    }
+}
+
+/// Where a value is placed in its node; see [`crate::place`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Placement {
+    /// A KDL Argument.
+    Argument,
+    /// A KDL Property.
+    Property,
+    /// A child node.
+    Child,
+    /// A node with a layout of its own; see [`crate::node`].
+    Node,
+}
+
+impl Placement {
+    fn of_wrapper(name: &str) -> Option<Self> {
+        match name {
+            place::ARG => Some(Placement::Argument),
+            place::PROP => Some(Placement::Property),
+            place::CHILD => Some(Placement::Child),
+            _ => None,
+        }
+    }
+}
+
+/// How an integer is written; see [`crate::radix`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NumberStyle {
+    /// Decimal, like `1000000`.
+    Decimal,
+    /// Decimal with its digits in groups of three, like `1_000_000`.
+    Grouped,
+    /// Hexadecimal, like `0xF4240`.
+    Hex,
+    /// Octal, like `0o3641100`.
+    Octal,
+    /// Binary, like `0b11110100001001000000`.
+    Binary,
+}
+
+impl NumberStyle {
+    fn of_wrapper(name: &str) -> Option<Self> {
+        match name {
+            radix::GROUPED => Some(NumberStyle::Grouped),
+            radix::HEX => Some(NumberStyle::Hex),
+            radix::OCTAL => Some(NumberStyle::Octal),
+            radix::BINARY => Some(NumberStyle::Binary),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a struct is an arbitrary precision number, written as its text.
+#[cfg(feature = "arbitrary_precision")]
+fn is_number(name: &str) -> bool {
+    name == crate::number::TOKEN
+}
+
+#[cfg(not(feature = "arbitrary_precision"))]
+fn is_number(_: &str) -> bool {
+    false
 }
 
+/// How [`Serializer`] encodes Serde `map` entries.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MapFormat {
     Infer,
@@ -509,34 +1017,189 @@ pub enum MapFormat {
     Struct,
 }
 
-#[derive(Debug)]
+/// Which version of KDL [`Serializer`] writes.
+///
+/// Both are read. KDL v2 spells keywords with a `#`, as in `#true` and
+/// `#null`, and allows multi-line strings, which [`HumanFormatter`] writes for
+/// strings with line breaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+/// Options controlling which SiK extensions [`Serializer`] uses.
+///
+/// Every extension is off by default, producing base SiK.
+///
+/// ```
+/// # use serde_kdl::ser::{MapFormat, Options};
+/// let opt = Options::new()
+///     .option_as_enum(true)
+///     .map_format(MapFormat::Struct);
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Options {
+    /// Serialize `option` as if it were an `enum` of `None`/`Some`.
     pub option_as_enum: bool,
+    /// Serialize `unit_struct` as if it were a `tuple_struct` containing a single `null`.
+    pub unit_struct_as_tuple: bool,
+    /// Serialize `unit_variant` as if it were a `tuple_variant` containing a single `null`.
+    pub unit_variant_as_tuple: bool,
+    /// Serialize `newtype_*` as if it were a `tuple_*` of the wrapped value.
     pub newtype_as_tuple: bool,
+    /// How to encode map entries.
     pub map_format: MapFormat,
-    #[doc(hidden)]
-    pub _non_exhaustive_but_pub: (),
+    /// Serialize `seq` struct fields as one node per element, each named
+    /// after the field, rather than as a single node with children.
+    ///
+    /// An empty `seq` writes no nodes at all, so such fields should be
+    /// `#[serde(default)]` to deserialize again.
+    pub repeated_seq_fields: bool,
+    /// Serialize a struct's last field, if it is a non-empty `struct`, as
+    /// further fields of the parent struct's node, rather than as a node of
+    /// its own. Its fields must not share names with the parent's fields.
+    pub flatten_tail_struct: bool,
+    /// Serialize a struct's first field, if it is a non-empty `seq` of leaf
+    /// values, as the KDL Arguments of the struct's node, where the
+    /// formatter supports it.
+    pub leading_seq_as_arguments: bool,
+    /// Serialize a struct's first field, if it has this name and a string
+    /// value, as the type annotation of the struct's node, e.g. the tag of an
+    /// internally or adjacently tagged enum, `(Http)listener port=80`.
+    pub tag_as_type_annotation: Option<&'static str>,
+    /// How deeply values may nest, counting each `seq`, `map`, `struct`,
+    /// `option`, and `newtype_*`, before serializing fails with
+    /// [`Error::Limit`](crate::Error::Limit), rather than overflowing the
+    /// stack on a value which recursively contains itself.
+    pub max_depth: usize,
+    /// Which version of KDL to write.
+    pub version: Version,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
             option_as_enum: false,
+            unit_struct_as_tuple: false,
+            unit_variant_as_tuple: false,
             newtype_as_tuple: false,
             map_format: MapFormat::Infer,
-            _non_exhaustive_but_pub: (),
+            repeated_seq_fields: false,
+            flatten_tail_struct: false,
+            leading_seq_as_arguments: false,
+            tag_as_type_annotation: None,
+            max_depth: 128,
+            version: Version::V1,
         }
     }
 }
 
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn option_as_enum(mut self, yes: bool) -> Self {
+        self.option_as_enum = yes;
+        self
+    }
+
+    pub fn unit_struct_as_tuple(mut self, yes: bool) -> Self {
+        self.unit_struct_as_tuple = yes;
+        self
+    }
+
+    pub fn unit_variant_as_tuple(mut self, yes: bool) -> Self {
+        self.unit_variant_as_tuple = yes;
+        self
+    }
+
+    pub fn newtype_as_tuple(mut self, yes: bool) -> Self {
+        self.newtype_as_tuple = yes;
+        self
+    }
+
+    pub fn map_format(mut self, format: MapFormat) -> Self {
+        self.map_format = format;
+        self
+    }
+
+    pub fn repeated_seq_fields(mut self, yes: bool) -> Self {
+        self.repeated_seq_fields = yes;
+        self
+    }
+
+    pub fn flatten_tail_struct(mut self, yes: bool) -> Self {
+        self.flatten_tail_struct = yes;
+        self
+    }
+
+    pub fn leading_seq_as_arguments(mut self, yes: bool) -> Self {
+        self.leading_seq_as_arguments = yes;
+        self
+    }
+
+    pub fn tag_as_type_annotation(mut self, tag: Option<&'static str>) -> Self {
+        self.tag_as_type_annotation = tag;
+        self
+    }
+
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = max;
+        self
+    }
+
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+}
+
 /// Serde [`Serializer`](serde::Serializer) for KDL documents.
 ///
 /// Note that this serializer may only be used once
 #[derive(Debug)]
 pub struct Serializer<'a, F: Format> {
     opt: Options,
-    sink: &'a mut F::Sink,
-    fmt: F,
+    pub(crate) sink: &'a mut F::Sink,
+    pub(crate) fmt: F,
+    /// The structs currently being serialized, innermost last.
+    structs: Vec<StructState>,
+    /// The string key of the map entry being written.
+    map_key: Option<String>,
+    /// Whether the next seq element is written as a child node even if it
+    /// could be an argument, as a single argument would read back as a leaf
+    /// when deserialized without a type to guide it. A struct field's type
+    /// guides it, unless the struct is flattened, which serde writes as a map,
+    /// so only seqs outside struct fields are guarded, as in a
+    /// `serde_json::Value`.
+    lone_element: bool,
+    /// How deeply nested the value being written is; see
+    /// [`Options::max_depth`].
+    depth: usize,
+    /// Whether the struct being written is an arbitrary precision number,
+    /// whose one field is written as the number's text.
+    number: bool,
+    /// While the contents of a [`Document`](crate::Document) are being
+    /// written, the marker of the token whose text comes next, if any.
+    document: Option<Option<&'static str>>,
+}
+
+#[derive(Debug)]
+struct StructState {
+    /// How many fields are left to serialize.
+    remaining: usize,
+    /// The names of the fields written to this struct's node so far,
+    /// including those of its parents if it is flattened into them.
+    names: Vec<&'static str>,
+    /// Whether this struct is flattened into its parent's node; see
+    /// [`Options::flatten_tail_struct`].
+    flattened: bool,
+    /// Whether this struct's group is yet to begin, as its first field may
+    /// be its type annotation; see [`Options::tag_as_type_annotation`].
+    pending_group: bool,
 }
 
 impl<'a, F: Format> Serializer<'a, F> {
@@ -544,10 +1207,57 @@ impl<'a, F: Format> Serializer<'a, F> {
         Self::new_with_options(sink, fmt, Options::default())
     }
 
-    pub fn new_with_options(sink: &'a mut F::Sink, fmt: F, opt: Options) -> Self {
-        Serializer { opt, sink, fmt }
+    pub fn new_with_options(sink: &'a mut F::Sink, mut fmt: F, opt: Options) -> Self {
+        fmt.set_version(opt.version);
+        Serializer {
+            opt,
+            sink,
+            fmt,
+            structs: Vec::new(),
+            map_key: None,
+            lone_element: false,
+            depth: 0,
+            number: false,
+            document: None,
+        }
     }
-}
+
+    /// Go one level deeper into the value being written.
+    fn enter(&mut self) -> Result {
+        if self.depth >= self.opt.max_depth {
+            return Err(crate::Error::Limit {
+                limit: Limit::Depth,
+                max: self.opt.max_depth,
+                location: None,
+            });
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Annotate the value being written with its `enum` variant. An `enum`
+    /// directly in a `newtype_variant` has no room for its own annotation.
+    fn annotate_variant(&mut self, variant: &'static str) -> Result {
+        if self.fmt.has_type_annotation() {
+            return Err(crate::Error::custom(format_args!(
+                "variant `{}` is directly in another variant, which needs `newtype_as_tuple`",
+                variant
+            )));
+        }
+        Ok(self.fmt.require_type_annotation(self.sink, variant)?)
+    }
+
+    /// Write a value wrapped in another with `f`, one level deeper.
+    fn nested<T, E: From<crate::Error>>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.enter()?;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+}
 
 macro_rules! forward_ser_to_write {
     ($($T:ident),* $(,)?) => {$(
@@ -559,7 +1269,7 @@ macro_rules! forward_ser_to_write {
     )*};
 }
 
-impl<'a, F: Format> serde::Serializer for &'a mut Serializer<'_, F> {
+impl<F: Format> serde::Serializer for &mut Serializer<'_, F> {
     type Ok = ();
     type Error = crate::Error;
     type SerializeSeq = Self;
@@ -586,22 +1296,26 @@ impl<'a, F: Format> serde::Serializer for &'a mut Serializer<'_, F> {
         Ok(self.fmt.write_bytes(self.sink, v)?)
     }
 
+    // an `option` in a `Some` annotated by `option_as_enum` is transparent
     fn serialize_none(self) -> Result {
-        if self.opt.option_as_enum {
+        if self.opt.option_as_enum && !self.fmt.has_type_annotation() {
             self.serialize_unit_variant("Option", 0, "None")
         } else {
             Ok(self.fmt.write_unit(self.sink)?)
         }
     }
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result
-    where
-        T: Serialize,
-    {
-        if self.opt.option_as_enum {
-            self.serialize_newtype_variant("Option", 1, "Some", value)
-        } else {
-            value.serialize(self)
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result {
+        if self.opt.option_as_enum && !self.fmt.has_type_annotation() {
+            return self.serialize_newtype_variant("Option", 1, "Some", value);
+        }
+        match value.serialize(NullSomeSerializer {
+            ser: self,
+            nested: false,
+        }) {
+            Err(ProbeError::Rejected) => self.nested(|ser| value.serialize(ser)),
+            Err(ProbeError::Error(e)) => Err(e),
+            Ok(()) => Ok(()),
         }
     }
 
@@ -610,55 +1324,73 @@ impl<'a, F: Format> serde::Serializer for &'a mut Serializer<'_, F> {
     }
 
     fn serialize_unit_struct(self, name: &'static str) -> Result {
-        self.fmt.provide_type_annotation(self.sink, name)?;
-        Ok(self.fmt.write_unit(self.sink)?)
+        if self.opt.unit_struct_as_tuple {
+            let mut tuple = self.serialize_tuple_struct(name, 1)?;
+            SerializeTupleStruct::serialize_field(&mut tuple, &())?;
+            SerializeTupleStruct::end(tuple)
+        } else {
+            self.fmt.provide_type_annotation(self.sink, name)?;
+            Ok(self.fmt.write_unit(self.sink)?)
+        }
     }
 
     fn serialize_unit_variant(
         self,
-        _name: &'static str,
-        _variant_index: u32,
+        name: &'static str,
+        variant_index: u32,
         variant: &'static str,
     ) -> Result {
-        self.fmt.require_type_annotation(self.sink, variant)?;
-        self.serialize_unit()
+        if self.opt.unit_variant_as_tuple {
+            let mut tuple = self.serialize_tuple_variant(name, variant_index, variant, 1)?;
+            SerializeTupleVariant::serialize_field(&mut tuple, &())?;
+            SerializeTupleVariant::end(tuple)
+        } else {
+            self.annotate_variant(variant)?;
+            self.serialize_unit()
+        }
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(self, name: &'static str, value: &T) -> Result
-    where
-        T: Serialize,
-    {
-        if self.opt.newtype_as_tuple {
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result {
+        if let Some(placement) = Placement::of_wrapper(name) {
+            self.fmt.set_placement(self.sink, placement)?;
+            self.nested(|ser| value.serialize(ser))
+        } else if let Some(style) = NumberStyle::of_wrapper(name) {
+            self.fmt.set_number_style(self.sink, style)?;
+            self.nested(|ser| value.serialize(ser))
+        } else if self.opt.newtype_as_tuple {
             let mut tuple = self.serialize_tuple_struct(name, 1)?;
             SerializeTupleStruct::serialize_field(&mut tuple, value)?;
             SerializeTupleStruct::end(tuple)
         } else {
             self.fmt.provide_type_annotation(self.sink, name)?;
-            value.serialize(self)
+            self.nested(|ser| value.serialize(ser))
         }
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
         self,
         name: &'static str,
         variant_index: u32,
         variant: &'static str,
         value: &T,
-    ) -> Result
-    where
-        T: Serialize,
-    {
+    ) -> Result {
         if self.opt.newtype_as_tuple {
             let mut tuple = self.serialize_tuple_variant(name, variant_index, variant, 1)?;
             SerializeTupleVariant::serialize_field(&mut tuple, value)?;
             SerializeTupleVariant::end(tuple)
         } else {
-            self.fmt.require_type_annotation(self.sink, variant)?;
-            value.serialize(self)
+            self.annotate_variant(variant)?;
+            self.nested(|ser| value.serialize(ser))
         }
     }
 
-    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.enter()?;
+        self.lone_element = len == Some(1);
         self.fmt.begin_group(self.sink)?;
         Ok(self)
     }
@@ -672,6 +1404,10 @@ impl<'a, F: Format> serde::Serializer for &'a mut Serializer<'_, F> {
         name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
+        if name == document::NAME {
+            self.document = Some(None);
+            return Ok(self);
+        }
         self.fmt.provide_type_annotation(self.sink, name)?;
         self.serialize_tuple(len)
     }
@@ -683,11 +1419,12 @@ impl<'a, F: Format> serde::Serializer for &'a mut Serializer<'_, F> {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        self.fmt.require_type_annotation(self.sink, variant)?;
+        self.annotate_variant(variant)?;
         self.serialize_tuple(len)
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.enter()?;
         match self.opt.map_format {
             MapFormat::Infer => self.fmt.begin_map(self.sink)?,
             MapFormat::Tuple | MapFormat::Struct => self.fmt.begin_group(self.sink)?,
@@ -695,8 +1432,23 @@ impl<'a, F: Format> serde::Serializer for &'a mut Serializer<'_, F> {
         Ok(self)
     }
 
-    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        self.fmt.begin_group(self.sink)?;
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        if is_number(name) {
+            self.number = true;
+            return Ok(self);
+        }
+        self.enter()?;
+        let pending_group =
+            self.opt.tag_as_type_annotation.is_some() && len > 0 && !self.fmt.has_type_annotation();
+        if !pending_group {
+            self.fmt.begin_group(self.sink)?;
+        }
+        self.structs.push(StructState {
+            remaining: len,
+            names: Vec::new(),
+            flattened: false,
+            pending_group,
+        });
         Ok(self)
     }
 
@@ -707,7 +1459,7 @@ impl<'a, F: Format> serde::Serializer for &'a mut Serializer<'_, F> {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.fmt.require_type_annotation(self.sink, variant)?;
+        self.annotate_variant(variant)?;
         self.serialize_struct(variant, len)
     }
 
@@ -716,94 +1468,110 @@ impl<'a, F: Format> serde::Serializer for &'a mut Serializer<'_, F> {
     }
 }
 
-impl<'a, F: Format> SerializeSeq for &'a mut Serializer<'_, F> {
+impl<F: Format> SerializeSeq for &mut Serializer<'_, F> {
     type Ok = ();
     type Error = crate::Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result
-    where
-        T: Serialize,
-    {
-        self.fmt.begin_field(&mut self.sink, None)?;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        self.fmt.begin_field(self.sink, None)?;
+        if std::mem::take(&mut self.lone_element) {
+            self.fmt.set_placement(self.sink, Placement::Child)?;
+        }
         value.serialize(&mut **self)?;
-        self.fmt.end_field(&mut self.sink)?;
+        self.fmt.end_field(self.sink)?;
         Ok(())
     }
 
     fn end(self) -> Result {
+        self.depth -= 1;
         self.fmt.end_group(self.sink)?;
         Ok(())
     }
 }
 
-impl<'a, F: Format> SerializeTuple for &'a mut Serializer<'_, F> {
+impl<F: Format> SerializeTuple for &mut Serializer<'_, F> {
     type Ok = ();
     type Error = crate::Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result
-    where
-        T: Serialize,
-    {
-        self.fmt.begin_field(&mut self.sink, None)?;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        self.fmt.begin_field(self.sink, None)?;
+        if std::mem::take(&mut self.lone_element) {
+            self.fmt.set_placement(self.sink, Placement::Child)?;
+        }
         value.serialize(&mut **self)?;
-        self.fmt.end_field(&mut self.sink)?;
+        self.fmt.end_field(self.sink)?;
         Ok(())
     }
 
     fn end(self) -> Result {
+        self.depth -= 1;
         self.fmt.end_group(self.sink)?;
         Ok(())
     }
 }
 
-impl<'a, F: Format> SerializeTupleStruct for &'a mut Serializer<'_, F> {
+impl<F: Format> SerializeTupleStruct for &mut Serializer<'_, F> {
     type Ok = ();
     type Error = crate::Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result
-    where
-        T: Serialize,
-    {
-        self.fmt.begin_field(&mut self.sink, None)?;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        if self.document.is_some() {
+            return match value.serialize(DocumentTokenSerializer { ser: self }) {
+                Err(ProbeError::Error(e)) => Err(e),
+                Err(ProbeError::Rejected) => {
+                    Err(crate::Error::custom("expected the contents of a Document"))
+                }
+                Ok(()) => Ok(()),
+            };
+        }
+        self.fmt.begin_field(self.sink, None)?;
         value.serialize(&mut **self)?;
-        self.fmt.end_field(&mut self.sink)?;
+        self.fmt.end_field(self.sink)?;
         Ok(())
     }
 
     fn end(self) -> Result {
+        if self.document.take().is_some() {
+            return Ok(());
+        }
+        self.depth -= 1;
         self.fmt.end_group(self.sink)?;
         Ok(())
     }
 }
 
-impl<'a, F: Format> SerializeTupleVariant for &'a mut Serializer<'_, F> {
+impl<F: Format> SerializeTupleVariant for &mut Serializer<'_, F> {
     type Ok = ();
     type Error = crate::Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result
-    where
-        T: Serialize,
-    {
-        self.fmt.begin_field(&mut self.sink, None)?;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        self.fmt.begin_field(self.sink, None)?;
         value.serialize(&mut **self)?;
-        self.fmt.end_field(&mut self.sink)?;
+        self.fmt.end_field(self.sink)?;
         Ok(())
     }
 
     fn end(self) -> Result {
+        self.depth -= 1;
         self.fmt.end_group(self.sink)?;
         Ok(())
     }
 }
 
-impl<'a, F: Format> SerializeMap for &'a mut Serializer<'_, F> {
+impl<F: Format> SerializeMap for &mut Serializer<'_, F> {
     type Ok = ();
     type Error = crate::Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result
-    where
-        T: Serialize,
-    {
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result {
+        match key.serialize(FieldNameSerializer) {
+            Err(ProbeError::Rejected) => {}
+            Err(ProbeError::Error(e)) => return Err(e),
+            Ok(key) if self.opt.map_format == MapFormat::Infer || key == document::NAME => {
+                self.map_key = Some(key);
+                return Ok(());
+            }
+            Ok(_) => {}
+        }
         match self.opt.map_format {
             MapFormat::Infer => self.fmt.begin_map_key(self.sink)?,
             MapFormat::Tuple => self.fmt.begin_field(self.sink, None)?,
@@ -817,10 +1585,18 @@ impl<'a, F: Format> SerializeMap for &'a mut Serializer<'_, F> {
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result
-    where
-        T: Serialize,
-    {
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        if let Some(key) = self.map_key.take() {
+            if key == document::NAME {
+                return value.serialize(&mut **self);
+            }
+            self.fmt.begin_named_map_value(self.sink, &key)?;
+            // maps are ordered, so their entries are never properties
+            self.fmt.set_placement(self.sink, Placement::Child)?;
+            value.serialize(&mut **self)?;
+            self.fmt.end_named_map_value(self.sink)?;
+            return Ok(());
+        }
         match self.opt.map_format {
             MapFormat::Infer => self.fmt.begin_map_value(self.sink)?,
             MapFormat::Tuple => self.fmt.begin_field(self.sink, None)?,
@@ -835,6 +1611,7 @@ impl<'a, F: Format> SerializeMap for &'a mut Serializer<'_, F> {
     }
 
     fn end(self) -> Result {
+        self.depth -= 1;
         match self.opt.map_format {
             MapFormat::Infer => self.fmt.end_map(self.sink)?,
             MapFormat::Tuple | MapFormat::Struct => self.fmt.end_group(self.sink)?,
@@ -843,44 +1620,1131 @@ impl<'a, F: Format> SerializeMap for &'a mut Serializer<'_, F> {
     }
 }
 
-impl<'a, F: Format> SerializeStruct for &'a mut Serializer<'_, F> {
+impl<F: Format> SerializeStruct for &mut Serializer<'_, F> {
     type Ok = ();
     type Error = crate::Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result
-    where
-        T: Serialize,
-    {
-        self.fmt.begin_field(self.sink, Some(key))?;
-        value.serialize(&mut **self)?;
-        self.fmt.end_field(self.sink)?;
-        Ok(())
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result {
+        if self.number {
+            return self.serialize_number(value);
+        }
+        self.serialize_struct_field(key, value)
     }
 
     fn end(self) -> Result {
-        self.fmt.end_group(self.sink)?;
-        Ok(())
+        if std::mem::take(&mut self.number) {
+            return Ok(());
+        }
+        self.end_struct()
     }
 }
 
-impl<'a, F: Format> SerializeStructVariant for &'a mut Serializer<'_, F> {
+impl<F: Format> SerializeStructVariant for &mut Serializer<'_, F> {
     type Ok = ();
     type Error = crate::Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result
-    where
-        T: Serialize,
-    {
-        self.fmt.begin_field(self.sink, Some(key))?;
-        value.serialize(&mut **self)?;
-        self.fmt.end_field(self.sink)?;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result {
+        self.serialize_struct_field(key, value)
+    }
+
+    fn end(self) -> Result {
+        self.end_struct()
+    }
+}
+
+impl<F: Format> Serializer<'_, F> {
+    fn serialize_struct_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result {
+        if self.begin_pending_group(key, value)? {
+            return Ok(());
+        }
+        let state = self
+            .structs
+            .last_mut()
+            .expect("struct field outside of a struct");
+        state.remaining = state.remaining.saturating_sub(1);
+        let is_head = state.names.is_empty() && !state.flattened;
+        let is_tail = state.remaining == 0;
+        if state.flattened && state.names.contains(&key) {
+            return Err(crate::Error::custom(format_args!(
+                "cannot flatten tail struct with field `{}`, \
+                 as its parent has a field of the same name",
+                key
+            )));
+        }
+        state.names.push(key);
+        let tail = (self.opt.flatten_tail_struct && is_tail).then(|| state.names.clone());
+        let arguments =
+            self.opt.leading_seq_as_arguments && is_head && self.fmt.can_place_argument();
+        let repeated = self.opt.repeated_seq_fields;
+        value.serialize(FieldSerializer {
+            ser: self,
+            name: key,
+            arguments,
+            repeated,
+            tail,
+            wrappers: Vec::new(),
+        })
+    }
+
+    /// Begin the innermost struct's group if it is pending, returning whether
+    /// the field was written as the group's type annotation.
+    fn begin_pending_group<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<bool> {
+        let state = self
+            .structs
+            .last_mut()
+            .expect("struct field outside of a struct");
+        if !std::mem::take(&mut state.pending_group) {
+            return Ok(false);
+        }
+        if self.opt.tag_as_type_annotation == Some(key) {
+            match value.serialize(FieldNameSerializer) {
+                Err(ProbeError::Rejected) => {}
+                Err(ProbeError::Error(e)) => return Err(e),
+                Ok(ty) => {
+                    state.remaining -= 1;
+                    self.fmt.require_dynamic_type_annotation(self.sink, &ty)?;
+                    self.fmt.begin_group(self.sink)?;
+                    return Ok(true);
+                }
+            }
+        }
+        self.fmt.begin_group(self.sink)?;
+        Ok(false)
+    }
+
+    /// Write the text of an arbitrary precision number.
+    fn serialize_number<T: ?Sized + Serialize>(&mut self, text: &T) -> Result {
+        match text.serialize(FieldNameSerializer) {
+            Ok(text) if read::is_valid_number(&text) => {
+                Ok(self.fmt.write_number(self.sink, &text)?)
+            }
+            Err(ProbeError::Error(e)) => Err(e),
+            _ => Err(crate::Error::custom("expected the text of a number")),
+        }
+    }
+
+    /// Write the next token of a [`Document`]'s contents into the node
+    /// being written; see [`document::Token`].
+    fn write_document_token(&mut self, token: document::Token<'_>) -> Result {
+        use document::Token::*;
+        let pending = self.document.as_mut().and_then(Option::take);
+        match (pending, token) {
+            (None, Marker(marker @ ("property" | "type" | "string" | "number" | "node"))) => {
+                self.document = Some(Some(marker));
+            }
+            (None, Marker("argument")) => {
+                self.fmt.begin_field(self.sink, None)?;
+                self.fmt.set_placement(self.sink, Placement::Argument)?;
+            }
+            (Some("property"), Text(name)) => {
+                self.fmt.begin_named_map_value(self.sink, name)?;
+                self.fmt.set_placement(self.sink, Placement::Property)?;
+            }
+            (Some("type"), Text(ty)) => self.fmt.require_dynamic_type_annotation(self.sink, ty)?,
+            (Some("string"), Text(v)) => {
+                self.fmt.write_string(self.sink, v)?;
+                self.fmt.end_field(self.sink)?;
+            }
+            (Some("number"), Text(v)) if read::is_valid_number(v) => {
+                self.fmt.write_number(self.sink, v)?;
+                self.fmt.end_field(self.sink)?;
+            }
+            (None, Bool(v)) => {
+                self.fmt.write_bool(self.sink, v)?;
+                self.fmt.end_field(self.sink)?;
+            }
+            (None, Marker("null")) => {
+                self.fmt.write_unit(self.sink)?;
+                self.fmt.end_field(self.sink)?;
+            }
+            (Some("node"), Text("-")) => self.fmt.begin_field(self.sink, None)?,
+            (Some("node"), Text(name)) => self.fmt.begin_named_map_value(self.sink, name)?,
+            (None, Marker("leaf")) => {
+                self.fmt.set_placement(self.sink, Placement::Node)?;
+                self.fmt.begin_group(self.sink)?;
+            }
+            (None, Marker("block")) => self.fmt.begin_group(self.sink)?,
+            (None, Marker("end")) => {
+                self.fmt.end_group(self.sink)?;
+                self.fmt.end_field(self.sink)?;
+            }
+            _ => return Err(crate::Error::custom("expected the contents of a Document")),
+        }
         Ok(())
     }
 
+    /// Write a `some` whose value would be written as `null` as `(Some)null`.
+    fn write_null_some(&mut self, nested: bool) -> Result {
+        if nested || self.fmt.has_type_annotation() {
+            return Err(crate::Error::custom(
+                "`Some` of a `null` value here would read back as `None`",
+            ));
+        }
+        self.fmt.require_type_annotation(self.sink, "Some")?;
+        Ok(self.fmt.write_unit(self.sink)?)
+    }
+
+    fn end_struct(&mut self) -> Result {
+        let state = self.structs.pop().expect("struct end outside of a struct");
+        if state.pending_group {
+            self.fmt.begin_group(self.sink)?;
+        }
+        if !state.flattened {
+            self.depth -= 1;
+            self.fmt.end_group(self.sink)?;
+        }
+        Ok(())
+    }
+}
+
+/// The error of a serializer probing whether a value is of a given shape.
+///
+/// [`ProbeError::Rejected`] means nothing was written, and the value should
+/// be serialized normally instead.
+#[derive(Debug)]
+enum ProbeError {
+    Rejected,
+    Error(crate::Error),
+}
+
+impl std::error::Error for ProbeError {}
+
+impl From<crate::Error> for ProbeError {
+    fn from(e: crate::Error) -> Self {
+        ProbeError::Error(e)
+    }
+}
+
+impl From<io::Error> for ProbeError {
+    fn from(e: io::Error) -> Self {
+        ProbeError::Error(e.into())
+    }
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::Rejected => f.write_str("value rejected by probe"),
+            ProbeError::Error(e) => e.fmt(f),
+        }
+    }
+}
+
+impl serde::ser::Error for ProbeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        ProbeError::Error(crate::Error::custom(message))
+    }
+}
+
+macro_rules! reject {
+    ($($method:ident($($T:ty),*) -> $Ok:ty;)*) => {$(
+        fn $method(self, $(_: $T),*) -> Result<$Ok, ProbeError> {
+            Err(ProbeError::Rejected)
+        }
+    )*};
+}
+
+/// Serializes a `some` whose value would be written as `null`, and so read
+/// back as `none`, as `(Some)null`.
+///
+/// Any other value is rejected before anything is written.
+struct NullSomeSerializer<'s, 'a, F: Format> {
+    ser: &'s mut Serializer<'a, F>,
+    /// Whether the value is in a `some` of its own, such as the `None` of
+    /// `Some(Some(None))`, which would need a second `(Some)`.
+    nested: bool,
+}
+
+impl<F: Format> NullSomeSerializer<'_, '_, F> {
+    fn write(self) -> Result<(), ProbeError> {
+        Ok(self.ser.write_null_some(self.nested)?)
+    }
+}
+
+impl<F: Format> serde::Serializer for NullSomeSerializer<'_, '_, F> {
+    type Ok = ();
+    type Error = ProbeError;
+    type SerializeSeq = Impossible<(), ProbeError>;
+    type SerializeTuple = Impossible<(), ProbeError>;
+    type SerializeTupleStruct = Impossible<(), ProbeError>;
+    type SerializeTupleVariant = Impossible<(), ProbeError>;
+    type SerializeMap = Impossible<(), ProbeError>;
+    type SerializeStruct = Impossible<(), ProbeError>;
+    type SerializeStructVariant = Impossible<(), ProbeError>;
+
+    reject! {
+        serialize_bool(bool) -> ();
+        serialize_i8(i8) -> ();
+        serialize_i16(i16) -> ();
+        serialize_i32(i32) -> ();
+        serialize_i64(i64) -> ();
+        serialize_i128(i128) -> ();
+        serialize_u8(u8) -> ();
+        serialize_u16(u16) -> ();
+        serialize_u32(u32) -> ();
+        serialize_u64(u64) -> ();
+        serialize_u128(u128) -> ();
+        serialize_f32(f32) -> ();
+        serialize_f64(f64) -> ();
+        serialize_char(char) -> ();
+        serialize_str(&str) -> ();
+        serialize_bytes(&[u8]) -> ();
+        serialize_unit_variant(&'static str, u32, &'static str) -> ();
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_none(self) -> Result<(), ProbeError> {
+        self.write()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), ProbeError> {
+        value.serialize(NullSomeSerializer {
+            nested: true,
+            ..self
+        })
+    }
+
+    fn serialize_unit(self) -> Result<(), ProbeError> {
+        self.write()
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), ProbeError> {
+        if self.ser.opt.unit_struct_as_tuple {
+            Err(ProbeError::Rejected)
+        } else {
+            self.write()
+        }
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), ProbeError> {
+        Err(ProbeError::Rejected)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), ProbeError> {
+        Err(ProbeError::Rejected)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        true
+    }
+}
+
+/// Serializes a token of a [`Document`](crate::Document)'s contents.
+///
+/// Anything else is rejected.
+struct DocumentTokenSerializer<'s, 'a, F: Format> {
+    ser: &'s mut Serializer<'a, F>,
+}
+
+impl<F: Format> serde::Serializer for DocumentTokenSerializer<'_, '_, F> {
+    type Ok = ();
+    type Error = ProbeError;
+    type SerializeSeq = Impossible<(), ProbeError>;
+    type SerializeTuple = Impossible<(), ProbeError>;
+    type SerializeTupleStruct = Impossible<(), ProbeError>;
+    type SerializeTupleVariant = Impossible<(), ProbeError>;
+    type SerializeMap = Impossible<(), ProbeError>;
+    type SerializeStruct = Impossible<(), ProbeError>;
+    type SerializeStructVariant = Impossible<(), ProbeError>;
+
+    reject! {
+        serialize_i8(i8) -> ();
+        serialize_i16(i16) -> ();
+        serialize_i32(i32) -> ();
+        serialize_i64(i64) -> ();
+        serialize_i128(i128) -> ();
+        serialize_u8(u8) -> ();
+        serialize_u16(u16) -> ();
+        serialize_u32(u32) -> ();
+        serialize_u64(u64) -> ();
+        serialize_u128(u128) -> ();
+        serialize_f32(f32) -> ();
+        serialize_f64(f64) -> ();
+        serialize_char(char) -> ();
+        serialize_bytes(&[u8]) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(&'static str) -> ();
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<(), ProbeError> {
+        Ok(self.ser.write_document_token(document::Token::Bool(v))?)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), ProbeError> {
+        Ok(self.ser.write_document_token(document::Token::Text(v))?)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Result<(), ProbeError> {
+        Err(ProbeError::Rejected)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), ProbeError> {
+        if name != document::NAME {
+            return Err(ProbeError::Rejected);
+        }
+        Ok(self
+            .ser
+            .write_document_token(document::Token::Marker(variant))?)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), ProbeError> {
+        Err(ProbeError::Rejected)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), ProbeError> {
+        Err(ProbeError::Rejected)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        true
+    }
+}
+
+/// Serializes a map key as a struct field name, or an enum tag as a type
+/// annotation, if it is a string.
+///
+/// Any other value is rejected.
+struct FieldNameSerializer;
+
+impl serde::Serializer for FieldNameSerializer {
+    type Ok = String;
+    type Error = ProbeError;
+    type SerializeSeq = Impossible<String, ProbeError>;
+    type SerializeTuple = Impossible<String, ProbeError>;
+    type SerializeTupleStruct = Impossible<String, ProbeError>;
+    type SerializeTupleVariant = Impossible<String, ProbeError>;
+    type SerializeMap = Impossible<String, ProbeError>;
+    type SerializeStruct = Impossible<String, ProbeError>;
+    type SerializeStructVariant = Impossible<String, ProbeError>;
+
+    reject! {
+        serialize_bool(bool) -> String;
+        serialize_i8(i8) -> String;
+        serialize_i16(i16) -> String;
+        serialize_i32(i32) -> String;
+        serialize_i64(i64) -> String;
+        serialize_i128(i128) -> String;
+        serialize_u8(u8) -> String;
+        serialize_u16(u16) -> String;
+        serialize_u32(u32) -> String;
+        serialize_u64(u64) -> String;
+        serialize_u128(u128) -> String;
+        serialize_f32(f32) -> String;
+        serialize_f64(f64) -> String;
+        serialize_bytes(&[u8]) -> String;
+        serialize_none() -> String;
+        serialize_unit() -> String;
+        serialize_unit_struct(&'static str) -> String;
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, ProbeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, ProbeError> {
+        match v {
+            // would read back as a literal node
+            "-" => Err(ProbeError::Rejected),
+            _ => Ok(v.to_owned()),
+        }
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Result<String, ProbeError> {
+        Err(ProbeError::Rejected)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, ProbeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, ProbeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<String, ProbeError> {
+        Err(ProbeError::Rejected)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        true
+    }
+}
+
+/// A `some` or newtype struct which a struct field's value is wrapped in.
+#[derive(Debug, Clone, Copy)]
+enum Wrapper {
+    Some,
+    Newtype(&'static str),
+}
+
+/// Serializes a struct field, choosing how to write it from the first call
+/// its value makes, so that the value is only serialized once.
+///
+/// The `some`s and newtype structs around the value are unwrapped until its
+/// shape is known. A value which isn't written in a shape of its own is
+/// written as the field's node, as if it had been serialized directly.
+struct FieldSerializer<'s, 'a, F: Format> {
+    ser: &'s mut Serializer<'a, F>,
+    name: &'static str,
+    /// Whether a non-empty `seq` is written as the node's arguments; see
+    /// [`Options::leading_seq_as_arguments`].
+    arguments: bool,
+    /// Whether a `seq` is written as one node per element; see
+    /// [`Options::repeated_seq_fields`].
+    repeated: bool,
+    /// The names of the fields already in the parent's node, if a non-empty
+    /// `struct` is flattened into it; see [`Options::flatten_tail_struct`].
+    tail: Option<Vec<&'static str>>,
+    /// The wrappers unwrapped so far, outermost first.
+    wrappers: Vec<Wrapper>,
+}
+
+impl<'s, 'a, F: Format> FieldSerializer<'s, 'a, F> {
+    /// Serialize the value inside `wrapper`, one level deeper.
+    fn unwrap<T: ?Sized + Serialize>(mut self, wrapper: Wrapper, value: &T) -> Result {
+        self.wrappers.push(wrapper);
+        let FieldSerializer {
+            ser,
+            name,
+            arguments,
+            repeated,
+            tail,
+            wrappers,
+        } = self;
+        ser.nested(|ser| {
+            value.serialize(FieldSerializer {
+                ser,
+                name,
+                arguments,
+                repeated,
+                tail,
+                wrappers,
+            })
+        })
+    }
+
+    /// Begin the field's node, and write what its newtype struct wrappers
+    /// would have. The `some`s are transparent for anything but `null`.
+    fn begin_node(self) -> Result<&'s mut Serializer<'a, F>> {
+        let ser = self.ser;
+        ser.fmt.begin_field(ser.sink, Some(self.name))?;
+        for wrapper in self.wrappers {
+            if let Wrapper::Newtype(name) = wrapper {
+                if let Some(placement) = Placement::of_wrapper(name) {
+                    ser.fmt.set_placement(ser.sink, placement)?;
+                } else if let Some(style) = NumberStyle::of_wrapper(name) {
+                    ser.fmt.set_number_style(ser.sink, style)?;
+                } else {
+                    ser.fmt.provide_type_annotation(ser.sink, name)?;
+                }
+            }
+        }
+        Ok(ser)
+    }
+
+    /// Write the field's node with `f`.
+    fn node(self, f: impl FnOnce(&mut Serializer<'a, F>) -> Result) -> Result {
+        let ser = self.begin_node()?;
+        f(ser)?;
+        Ok(ser.fmt.end_field(ser.sink)?)
+    }
+
+    /// Write the field's node as a value which reads back as `none`, so as
+    /// `(Some)null` if it is directly in a `some`.
+    fn null(mut self, f: impl FnOnce(&mut Serializer<'a, F>) -> Result) -> Result {
+        let somes = (self.wrappers.iter().rev())
+            .take_while(|wrapper| matches!(wrapper, Wrapper::Some))
+            .count();
+        self.wrappers.truncate(self.wrappers.len() - somes);
+        match somes {
+            0 => self.node(f),
+            _ => self.node(|ser| ser.write_null_some(somes > 1)),
+        }
+    }
+}
+
+macro_rules! forward_field_leaf {
+    ($($method:ident($($arg:ident: $T:ty),*);)*) => {$(
+        fn $method(self, $($arg: $T),*) -> Result {
+            self.node(|ser| ser.$method($($arg),*))
+        }
+    )*};
+}
+
+macro_rules! forward_field_compound {
+    ($($method:ident($($arg:ident: $T:ty),*) -> $Ok:ty;)*) => {$(
+        fn $method(self, $($arg: $T),*) -> Result<$Ok> {
+            let ser = self.begin_node()?;
+            ser.$method($($arg),*)?;
+            Ok(FieldCompound::Node(ser))
+        }
+    )*};
+}
+
+impl<'s, 'a, F: Format> serde::Serializer for FieldSerializer<'s, 'a, F> {
+    type Ok = ();
+    type Error = crate::Error;
+    type SerializeSeq = FieldCompound<'s, 'a, F>;
+    type SerializeTuple = FieldCompound<'s, 'a, F>;
+    type SerializeTupleStruct = FieldCompound<'s, 'a, F>;
+    type SerializeTupleVariant = FieldCompound<'s, 'a, F>;
+    type SerializeMap = FieldCompound<'s, 'a, F>;
+    type SerializeStruct = FieldCompound<'s, 'a, F>;
+    type SerializeStructVariant = FieldCompound<'s, 'a, F>;
+
+    forward_field_leaf! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str);
+    }
+
+    forward_field_compound! {
+        serialize_tuple(len: usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(name: &'static str, variant_index: u32, variant: &'static str, len: usize) -> Self::SerializeTupleVariant;
+        serialize_map(len: Option<usize>) -> Self::SerializeMap;
+        serialize_struct_variant(name: &'static str, variant_index: u32, variant: &'static str, len: usize) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_none(self) -> Result {
+        self.null(|ser| ser.serialize_none())
+    }
+
+    fn serialize_unit(self) -> Result {
+        self.null(|ser| ser.serialize_unit())
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result {
+        if self.ser.opt.unit_struct_as_tuple {
+            self.node(|ser| ser.serialize_unit_struct(name))
+        } else {
+            self.null(|ser| ser.serialize_unit_struct(name))
+        }
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result {
+        if self.ser.opt.option_as_enum {
+            self.node(|ser| ser.serialize_some(value))
+        } else {
+            self.unwrap(Wrapper::Some, value)
+        }
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result {
+        if self.ser.opt.newtype_as_tuple && !place::is_wrapper(name) {
+            self.node(|ser| ser.serialize_newtype_struct(name, value))
+        } else {
+            self.unwrap(Wrapper::Newtype(name), value)
+        }
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result {
+        self.node(|ser| ser.serialize_newtype_variant(name, variant_index, variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        let name = self.name;
+        // an empty seq would leave no trace of the field
+        if self.arguments && len != Some(0) {
+            Ok(FieldCompound::Arguments {
+                ser: self.ser,
+                name,
+            })
+        } else if self.repeated {
+            Ok(FieldCompound::Repeated {
+                ser: self.ser,
+                name,
+            })
+        } else {
+            let ser = self.begin_node()?;
+            ser.serialize_seq(len)?;
+            ser.lone_element = false;
+            Ok(FieldCompound::Node(ser))
+        }
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        match self.tail {
+            // an empty struct would leave no trace of the field
+            Some(names) if len > 0 && !is_number(name) => {
+                self.ser.structs.push(StructState {
+                    remaining: len,
+                    names,
+                    flattened: true,
+                    pending_group: false,
+                });
+                Ok(FieldCompound::Flattened(self.ser))
+            }
+            _ => {
+                let ser = self.begin_node()?;
+                ser.serialize_struct(name, len)?;
+                Ok(FieldCompound::Node(ser))
+            }
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        true
+    }
+}
+
+/// The compound value of a struct field, written as chosen by
+/// [`FieldSerializer`].
+enum FieldCompound<'s, 'a, F: Format> {
+    /// A `seq` written as its node's arguments.
+    Arguments {
+        ser: &'s mut Serializer<'a, F>,
+        name: &'static str,
+    },
+    /// A `seq` written as one node per element.
+    Repeated {
+        ser: &'s mut Serializer<'a, F>,
+        name: &'static str,
+    },
+    /// A `struct` flattened into its parent's node.
+    Flattened(&'s mut Serializer<'a, F>),
+    /// A value written as the field's node.
+    Node(&'s mut Serializer<'a, F>),
+}
+
+impl<'s, 'a, F: Format> FieldCompound<'s, 'a, F> {
+    fn node(&mut self) -> &mut &'s mut Serializer<'a, F> {
+        match self {
+            FieldCompound::Node(ser) => ser,
+            _ => unreachable!("only a seq or struct is written in a shape of its own"),
+        }
+    }
+
+    /// End the field's node with `end`.
+    fn end_node(self, end: impl FnOnce(&mut Serializer<'a, F>) -> Result) -> Result {
+        match self {
+            FieldCompound::Node(ser) => {
+                end(ser)?;
+                Ok(ser.fmt.end_field(ser.sink)?)
+            }
+            _ => unreachable!("only a seq or struct is written in a shape of its own"),
+        }
+    }
+}
+
+impl<F: Format> SerializeSeq for FieldCompound<'_, '_, F> {
+    type Ok = ();
+    type Error = crate::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        match self {
+            FieldCompound::Arguments { ser, name } => {
+                ser.fmt.begin_field(ser.sink, None)?;
+                ser.fmt.set_placement(ser.sink, Placement::Argument)?;
+                value.serialize(ArgumentSerializer {
+                    ser: &mut **ser,
+                    name,
+                })?;
+                Ok(ser.fmt.end_field(ser.sink)?)
+            }
+            FieldCompound::Repeated { ser, name } => {
+                ser.fmt.begin_field(ser.sink, Some(name))?;
+                // properties can't repeat
+                ser.fmt.set_placement(ser.sink, Placement::Child)?;
+                value.serialize(&mut **ser)?;
+                Ok(ser.fmt.end_field(ser.sink)?)
+            }
+            node => SerializeSeq::serialize_element(node.node(), value),
+        }
+    }
+
     fn end(self) -> Result {
-        self.fmt.end_group(self.sink)?;
+        match self {
+            FieldCompound::Arguments { .. } | FieldCompound::Repeated { .. } => Ok(()),
+            node => node.end_node(|ser| SerializeSeq::end(ser)),
+        }
+    }
+}
+
+impl<F: Format> SerializeTuple for FieldCompound<'_, '_, F> {
+    type Ok = ();
+    type Error = crate::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        SerializeTuple::serialize_element(self.node(), value)
+    }
+
+    fn end(self) -> Result {
+        self.end_node(|ser| SerializeTuple::end(ser))
+    }
+}
+
+impl<F: Format> SerializeTupleStruct for FieldCompound<'_, '_, F> {
+    type Ok = ();
+    type Error = crate::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        SerializeTupleStruct::serialize_field(self.node(), value)
+    }
+
+    fn end(self) -> Result {
+        self.end_node(|ser| SerializeTupleStruct::end(ser))
+    }
+}
+
+impl<F: Format> SerializeTupleVariant for FieldCompound<'_, '_, F> {
+    type Ok = ();
+    type Error = crate::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        SerializeTupleVariant::serialize_field(self.node(), value)
+    }
+
+    fn end(self) -> Result {
+        self.end_node(|ser| SerializeTupleVariant::end(ser))
+    }
+}
+
+impl<F: Format> SerializeMap for FieldCompound<'_, '_, F> {
+    type Ok = ();
+    type Error = crate::Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result {
+        SerializeMap::serialize_key(self.node(), key)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        SerializeMap::serialize_value(self.node(), value)
+    }
+
+    fn end(self) -> Result {
+        self.end_node(|ser| SerializeMap::end(ser))
+    }
+}
+
+impl<F: Format> SerializeStruct for FieldCompound<'_, '_, F> {
+    type Ok = ();
+    type Error = crate::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result {
+        match self {
+            FieldCompound::Flattened(ser) => ser.serialize_struct_field(key, value),
+            node => SerializeStruct::serialize_field(node.node(), key, value),
+        }
+    }
+
+    fn end(self) -> Result {
+        match self {
+            FieldCompound::Flattened(ser) => ser.end_struct(),
+            node => node.end_node(|ser| SerializeStruct::end(ser)),
+        }
+    }
+}
+
+impl<F: Format> SerializeStructVariant for FieldCompound<'_, '_, F> {
+    type Ok = ();
+    type Error = crate::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result {
+        SerializeStructVariant::serialize_field(self.node(), key, value)
+    }
+
+    fn end(self) -> Result {
+        self.end_node(|ser| SerializeStructVariant::end(ser))
+    }
+}
+
+/// Serializes a leaf value as a KDL Argument, rejecting compound values.
+struct ArgumentSerializer<'s, 'a, F: Format> {
+    ser: &'s mut Serializer<'a, F>,
+    /// The field the argument belongs to.
+    name: &'static str,
+}
+
+impl<F: Format> ArgumentSerializer<'_, '_, F> {
+    fn not_leaf(&self) -> crate::Error {
+        crate::Error::custom(format_args!(
+            "cannot write the elements of `{}` as arguments, as they are not leaf values",
+            self.name
+        ))
+    }
+}
+
+macro_rules! forward_leaf {
+    ($($method:ident($($arg:ident: $T:ty),*);)*) => {$(
+        fn $method(self, $($arg: $T),*) -> Result {
+            self.ser.$method($($arg),*)
+        }
+    )*};
+}
+
+macro_rules! reject_compound {
+    ($($method:ident($($T:ty),*) -> $Ok:ty;)*) => {$(
+        fn $method(self, $(_: $T),*) -> Result<$Ok> {
+            Err(self.not_leaf())
+        }
+    )*};
+}
+
+impl<F: Format> serde::Serializer for ArgumentSerializer<'_, '_, F> {
+    type Ok = ();
+    type Error = crate::Error;
+    type SerializeSeq = Impossible<(), crate::Error>;
+    type SerializeTuple = Impossible<(), crate::Error>;
+    type SerializeTupleStruct = Impossible<(), crate::Error>;
+    type SerializeTupleVariant = Impossible<(), crate::Error>;
+    type SerializeMap = Impossible<(), crate::Error>;
+    type SerializeStruct = Impossible<(), crate::Error>;
+    type SerializeStructVariant = Impossible<(), crate::Error>;
+
+    forward_leaf! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str);
+    }
+
+    reject_compound! {
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result {
+        if self.ser.opt.option_as_enum {
+            Err(self.not_leaf())
+        } else {
+            let name = self.name;
+            self.ser
+                .nested(|ser| value.serialize(ArgumentSerializer { ser, name }))
+        }
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result {
+        if self.ser.opt.newtype_as_tuple && !place::is_wrapper(name) {
+            Err(self.not_leaf())
+        } else {
+            if let Some(style) = NumberStyle::of_wrapper(name) {
+                self.ser.fmt.set_number_style(self.ser.sink, style)?;
+            }
+            let name = self.name;
+            self.ser
+                .nested(|ser| value.serialize(ArgumentSerializer { ser, name }))
+        }
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result {
+        Err(self.not_leaf())
+    }
+
+    fn is_human_readable(&self) -> bool {
+        true
+    }
+}
+
+/// Writes values to an `io::Write` one at a time, each as a top-level `-`
+/// node of a KDL document, as for a log-like document which a
+/// [`StreamDeserializer`](crate::de::StreamDeserializer) reads back.
+///
+/// Each value is built in memory and written with a single `write_all`,
+/// followed by a newline, so a value which fails to serialize writes
+/// nothing.
+///
+/// ```
+/// use serde_kdl::ser::StreamSerializer;
+///
+/// let mut log = Vec::new();
+/// let mut stream = StreamSerializer::new(&mut log);
+/// stream.serialize(&[1, 2])?;
+/// stream.serialize("done")?;
+/// assert_eq!(std::str::from_utf8(&log).unwrap(), "- 1 2\n- r\"done\"\n");
+/// # Ok::<_, serde_kdl::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct StreamSerializer<W> {
+    writer: W,
+    opt: Options,
+    human: bool,
+}
+
+impl<W: io::Write> StreamSerializer<W> {
+    /// Write each value with [`HumanFormatter::node`].
+    pub fn new(writer: W) -> Self {
+        StreamSerializer {
+            writer,
+            opt: Options::default(),
+            human: true,
+        }
+    }
+
+    /// Write each value with [`SimpleFormatter`], on a single line.
+    pub fn ugly(writer: W) -> Self {
+        StreamSerializer {
+            human: false,
+            ..Self::new(writer)
+        }
+    }
+
+    pub fn with_options(mut self, opt: Options) -> Self {
+        self.opt = opt;
+        self
+    }
+
+    /// Append `value` as the next node.
+    pub fn serialize<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        let opt = self.opt.clone();
+        let mut node = if self.human {
+            let mut buf = String::new();
+            value.serialize(&mut Serializer::new_with_options(
+                &mut buf,
+                HumanFormatter::node(),
+                opt,
+            ))?;
+            // the node is written as if after an earlier one
+            buf.strip_prefix('\n').unwrap_or(&buf).into()
+        } else {
+            let mut buf = Vec::new();
+            value.serialize(&mut Serializer::new_with_options(
+                &mut buf,
+                SimpleFormatter::default(),
+                opt,
+            ))?;
+            buf
+        };
+        node.push(b'\n');
+        self.writer.write_all(&node)?;
         Ok(())
     }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
 }
 
 pub fn to_writer_ugly<W, T>(writer: &mut W, value: &T) -> Result
@@ -888,7 +2752,15 @@ where
     W: ?Sized + io::Write,
     T: ?Sized + Serialize,
 {
-    let mut ser = Serializer::new(writer, SimpleFormatter::default());
+    to_writer_ugly_with_options(writer, value, Options::default())
+}
+
+pub fn to_writer_ugly_with_options<W, T>(writer: &mut W, value: &T, opt: Options) -> Result
+where
+    W: ?Sized + io::Write,
+    T: ?Sized + Serialize,
+{
+    let mut ser = Serializer::new_with_options(writer, SimpleFormatter::default(), opt);
     value.serialize(&mut ser)
 }
 
@@ -905,18 +2777,54 @@ pub fn to_string_ugly<T>(value: &T) -> Result<String>
 where
     T: ?Sized + Serialize,
 {
-    let bytes = to_vec_ugly(value)?;
-    let string = unsafe { String::from_utf8_unchecked(bytes) };
+    to_string_ugly_with_options(value, Options::default())
+}
+
+pub fn to_string_ugly_with_options<T>(value: &T, opt: Options) -> Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let mut writer = Vec::with_capacity(128);
+    to_writer_ugly_with_options(&mut writer, value, opt)?;
+    let string = unsafe { String::from_utf8_unchecked(writer) };
     Ok(string)
 }
 
+/// Serialize human-friendly SiK to an `io::Write`.
+///
+/// [`HumanFormatter`] only supports in-memory buffers, so the full
+/// document is built in memory before being written out.
+pub fn to_writer<W, T>(writer: &mut W, value: &T) -> Result
+where
+    W: ?Sized + io::Write,
+    T: ?Sized + Serialize,
+{
+    to_writer_with_options(writer, value, Options::default())
+}
+
+pub fn to_writer_with_options<W, T>(writer: &mut W, value: &T, opt: Options) -> Result
+where
+    W: ?Sized + io::Write,
+    T: ?Sized + Serialize,
+{
+    let string = to_string_with_options(value, opt)?;
+    writer.write_all(string.as_bytes())?;
+    Ok(())
+}
+
 pub fn to_string<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    to_string_with_options(value, Options::default())
+}
+
+pub fn to_string_with_options<T>(value: &T, opt: Options) -> Result<String>
 where
     T: ?Sized + Serialize,
 {
     let mut buf = String::new();
-    let mut ser = Serializer::new(&mut buf, HumanFormatter::default());
+    let mut ser = Serializer::new_with_options(&mut buf, HumanFormatter::default(), opt);
     value.serialize(&mut ser)?;
-    drop(ser);
     Ok(buf)
 }
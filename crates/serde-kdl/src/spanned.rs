@@ -0,0 +1,174 @@
+//! A wrapper recording where in the source a value was deserialized from.
+//!
+//! ```
+//! # use serde::Deserialize;
+//! use serde_kdl::Spanned;
+//!
+//! #[derive(Deserialize)]
+//! struct Listener {
+//!     port: Spanned<u32>,
+//! }
+//!
+//! let listener: Listener = serde_kdl::from_str("port 99999")?;
+//! if *listener.port > 65535 {
+//!     let start = listener.port.start();
+//!     assert_eq!((start.line, start.column), (1, 1));
+//!     assert_eq!(listener.port.span(), 0..10);
+//! }
+//! # Ok::<_, serde_kdl::Error>(())
+//! ```
+
+use {
+    crate::error::Location,
+    serde::{de, Deserialize, Deserializer, Serialize, Serializer},
+    std::{
+        cmp::Ordering,
+        fmt,
+        hash::{Hash, Hasher},
+        marker::PhantomData,
+        ops::{Deref, DerefMut, Range},
+    },
+};
+
+pub(crate) const NAME: &str = "$serde_kdl::private::Spanned";
+pub(crate) const START: &str = "$serde_kdl::private::Spanned::start";
+pub(crate) const END: &str = "$serde_kdl::private::Spanned::end";
+pub(crate) const VALUE: &str = "$serde_kdl::private::Spanned::value";
+pub(crate) const FIELDS: &[&str] = &[START, END, VALUE];
+
+/// A value along with the location of the node, argument, or property it
+/// was deserialized from.
+///
+/// A value read from a struct node's fields spans the whole node, and one
+/// read from a document's implied root spans the whole document. Values
+/// deserialized through a buffer, as for `#[serde(flatten)]` and untagged
+/// enums, can't be spanned.
+///
+/// This only deserializes from serde-kdl. It serializes as the wrapped
+/// value, and compares, orders, and hashes by it alone.
+#[derive(Debug, Clone, Copy)]
+pub struct Spanned<T> {
+    start: Location,
+    end: Location,
+    value: T,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, start: Location, end: Location) -> Self {
+        Spanned { start, end, value }
+    }
+
+    /// The byte range of the value in the source.
+    pub fn span(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+
+    pub fn start(&self) -> Location {
+        self.start
+    }
+
+    pub fn end(&self) -> Location {
+        self.end
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+/// A value with an empty span at the start of the source.
+impl<T> From<T> for Spanned<T> {
+    fn from(value: T) -> Self {
+        Spanned::new(value, Location::start(), Location::start())
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
+impl<T: PartialOrd> PartialOrd for Spanned<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<T: Ord> Ord for Spanned<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<T: Hash> Hash for Spanned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+
+impl<T: Serialize> Serialize for Spanned<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Spanned<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct(NAME, FIELDS, SpannedVisitor(PhantomData))
+    }
+}
+
+struct SpannedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> de::Visitor<'de> for SpannedVisitor<T> {
+    type Value = Spanned<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a spanned value from serde-kdl")
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Spanned<T>, A::Error> {
+        let mut location = |field| match map.next_key::<String>()? {
+            Some(key) if key == field => {
+                let (offset, line, column) = map.next_value()?;
+                Ok(Location {
+                    offset,
+                    line,
+                    column,
+                })
+            }
+            _ => Err(de::Error::custom("expected a spanned value from serde-kdl")),
+        };
+        let start = location(START)?;
+        let end = location(END)?;
+        match map.next_key::<String>()? {
+            Some(key) if key == VALUE => Ok(Spanned::new(map.next_value()?, start, end)),
+            _ => Err(de::Error::custom("expected a spanned value from serde-kdl")),
+        }
+    }
+}
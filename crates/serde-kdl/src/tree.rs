@@ -0,0 +1,101 @@
+use {
+    crate::{
+        read::{Event, Limits, Literal, Reader, Span},
+        Result,
+    },
+    std::borrow::Cow,
+};
+
+/// A parsed KDL node, borrowing from the source where possible.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Node<'de> {
+    pub ty: Option<Cow<'de, str>>,
+    pub name: Cow<'de, str>,
+    pub entries: Vec<Entry<'de>>,
+    pub children: Option<Vec<Node<'de>>>,
+    pub span: Span,
+}
+
+/// A KDL argument (with no name) or property (with a name).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Entry<'de> {
+    pub name: Option<Cow<'de, str>>,
+    pub ty: Option<Cow<'de, str>>,
+    pub value: Literal<'de>,
+    pub span: Span,
+}
+
+impl<'de> Node<'de> {
+    pub fn children(&self) -> &[Node<'de>] {
+        self.children.as_deref().unwrap_or_default()
+    }
+
+    pub fn is_literal(&self) -> bool {
+        self.name == "-"
+    }
+}
+
+/// Parse a full KDL document.
+///
+/// The returned node is the (implied) root node: it has no name, and its
+/// children are the top-level nodes of the document.
+pub(crate) fn parse(src: &str, limits: Limits) -> Result<Node<'_>> {
+    Ok(parse_with_skipped_nodes(src, limits)?.0)
+}
+
+/// Parse a full KDL document, along with the spans of the nodes commented
+/// out with a slashdash.
+pub(crate) fn parse_with_skipped_nodes(src: &str, limits: Limits) -> Result<(Node<'_>, Vec<Span>)> {
+    let mut reader = Reader::with_limits(src, limits);
+    let mut stack = vec![Node {
+        ty: None,
+        name: Cow::Borrowed(""),
+        entries: Vec::new(),
+        children: Some(Vec::new()),
+        span: Span {
+            start: 0,
+            end: src.len(),
+        },
+    }];
+    while let Some(event) = reader.next_event()? {
+        let node = stack.last_mut().unwrap();
+        match event {
+            Event::StartNode { ty, name, span } => stack.push(Node {
+                ty,
+                name,
+                entries: Vec::new(),
+                children: None,
+                span,
+            }),
+            Event::Argument { ty, value, span } => node.entries.push(Entry {
+                name: None,
+                ty,
+                value,
+                span,
+            }),
+            Event::Property {
+                name,
+                ty,
+                value,
+                span,
+            } => node.entries.push(Entry {
+                name: Some(name),
+                ty,
+                value,
+                span,
+            }),
+            Event::StartChildren { .. } => {
+                node.children.get_or_insert_with(Vec::new);
+            }
+            Event::EndChildren { span } => node.span.end = span.end,
+            Event::EndNode { span } => {
+                let mut node = stack.pop().unwrap();
+                node.span.end = node.span.end.max(span.end);
+                let parent = stack.last_mut().unwrap();
+                parent.children.get_or_insert_with(Vec::new).push(node);
+            }
+            Event::Comment { .. } => {}
+        }
+    }
+    Ok((stack.pop().unwrap(), reader.take_skipped_nodes()))
+}
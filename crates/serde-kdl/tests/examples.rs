@@ -50,8 +50,8 @@ fn cargo() {
         license-file r"LICENSE.md"
     }
     dependencies {
-        - key=r"nom" value=r"6.0.1"
-        - key=r"thiserror" value=r"1.0.22"
+        nom r"6.0.1"
+        thiserror r"1.0.22"
     }
     "###);
 }
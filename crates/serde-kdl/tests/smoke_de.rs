@@ -0,0 +1,1543 @@
+use {
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    serde_bytes::ByteBuf,
+    serde_json::{json, Value},
+    serde_kdl::{
+        de::{self, from_str, from_str_with_options, StreamDeserializer},
+        error::Limit,
+        read::{self, Event, Literal, Reader},
+        ser::{
+            self, to_string, to_string_ugly, to_string_ugly_with_options, to_string_with_options,
+            StreamSerializer,
+        },
+        Arg, Binary, Child, Document, Grouped, Hex, Octal, Prop, Spanned,
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        fmt::Debug,
+        io::{self, Read},
+    },
+};
+
+fn roundtrip<T>(value: T) -> serde_kdl::Result
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    for version in [ser::Version::V1, ser::Version::V2] {
+        let opt = ser::Options::new().version(version);
+        let human = to_string_with_options(&value, opt.clone())?;
+        assert_eq!(from_str::<T>(&human)?, value, "human: {}", human);
+        let ugly = to_string_ugly_with_options(&value, opt)?;
+        assert_eq!(from_str::<T>(&ugly)?, value, "ugly: {}", ugly);
+    }
+    Ok(())
+}
+
+#[test]
+fn primitives() -> serde_kdl::Result {
+    roundtrip(true)?;
+    roundtrip(0u32)?;
+    roundtrip(-1i32)?;
+    roundtrip(1.5f32)?;
+    roundtrip(u128::MAX)?;
+    roundtrip("Hello".to_string())?;
+    roundtrip(r##"quote " and hash "#"##.to_string())?;
+    roundtrip(ByteBuf::from(b"KDL".to_vec()))?;
+    roundtrip('🦀')?;
+    roundtrip(())?;
+    roundtrip(Some(0))?;
+    roundtrip(None::<i32>)?;
+    Ok(())
+}
+
+#[test]
+fn numbers() -> serde_kdl::Result {
+    assert_eq!(from_str::<u16>("- 0xFF_FF")?, 0xFFFF);
+    assert_eq!(from_str::<u32>("- 0o755")?, 0o755);
+    assert_eq!(from_str::<i8>("- -0b1000_0000")?, i8::MIN);
+    assert_eq!(from_str::<u64>("- +1_000_000")?, 1_000_000);
+    assert_eq!(from_str::<f64>("- 1.5e-3")?, 1.5e-3);
+    assert_eq!(from_str::<f64>("- 1_000.000_1")?, 1000.0001);
+    assert_eq!(from_str::<f32>("- 0x10")?, 16.0);
+    assert_eq!(from_str::<f64>("- #-inf")?, f64::NEG_INFINITY);
+    assert_eq!(from_str::<i128>(&format!("- {}", i128::MIN))?, i128::MIN);
+    assert_eq!(
+        from_str::<u128>("- 0xFFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF")?,
+        u128::MAX
+    );
+    roundtrip(i128::MIN)?;
+
+    let err = from_str::<u8>("- 0x1_00").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "number `0x1_00` is out of range for u8 at line 1 column 3"
+    );
+    let err = from_str::<u32>("- -1").unwrap_err();
+    assert!(err.to_string().contains("out of range for u32"), "{}", err);
+    let err = from_str::<u128>(&format!("- {}0", u128::MAX)).unwrap_err();
+    assert!(err.to_string().contains("out of range for u128"), "{}", err);
+    let err = from_str::<f32>("- 1e40").unwrap_err();
+    assert!(err.to_string().contains("out of range for f32"), "{}", err);
+    // rounded straight to an `f32`, not through an `f64`
+    assert_eq!(from_str::<f32>("- 16_777_217.000_000_001")?, 16_777_218.0);
+    // a float is never truncated
+    let err = from_str::<i32>("- 2.0").unwrap_err();
+    assert!(err.to_string().contains("floating point `2.0`"), "{}", err);
+
+    // `0xFE` has no exponent, whatever its `E`
+    assert_eq!(from_str::<Value>("- 0xFE")?, json!(254));
+    #[cfg(not(feature = "arbitrary_precision"))]
+    assert_eq!(from_str::<Value>("- 1e3")?, json!(1000.0));
+    Ok(())
+}
+
+#[cfg(feature = "arbitrary_precision")]
+#[test]
+fn arbitrary_precision() -> serde_kdl::Result {
+    use serde_kdl::Number;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Invoice {
+        id: Number,
+        total: Number,
+        taxes: Vec<Number>,
+    }
+
+    let kdl = "\nid 1234567890123456789012345678901234567890\ntotal 19.990\ntaxes 1e400 -7\n";
+    let invoice: Invoice = from_str(kdl)?;
+    assert_eq!(invoice.total.as_f64(), 19.99);
+    assert_eq!(invoice.taxes[1].as_i64(), Some(-7));
+    assert_eq!(to_string(&invoice)?, kdl);
+    roundtrip(invoice)?;
+
+    assert_eq!(from_str::<Number>("- +0_019.5_0")?.as_str(), "19.50");
+    assert_eq!(from_str::<Number>("- 0xFF")?.as_str(), "255");
+    assert!(from_str::<Number>("- #nan").is_err());
+
+    // buffered by serde, only a number no primitive holds exactly is a map
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Amount {
+        Whole(u64),
+        Float(f64),
+        Exact(Number),
+    }
+    assert_eq!(from_str::<Amount>("- 1_000")?, Amount::Whole(1000));
+    assert_eq!(from_str::<Amount>("- 19.99")?, Amount::Float(19.99));
+    assert_eq!(
+        from_str::<Amount>("- 19.990")?,
+        Amount::Exact("19.990".parse()?)
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "test_arbitrary_precision")]
+#[test]
+fn serde_json_arbitrary_precision() -> serde_kdl::Result {
+    let id = "98765432109876543210.0123456789";
+    let value: Value = from_str(&format!("- {}", id))?;
+    assert_eq!(value.to_string(), id);
+    assert_eq!(to_string(&value)?, format!("- {}", id));
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Permissions {
+    mode: Octal<u32>,
+    mask: Hex<i64>,
+    flags: Vec<Binary<u8>>,
+    quota: Grouped<u64>,
+}
+
+#[test]
+fn number_styles() -> serde_kdl::Result {
+    roundtrip(Permissions {
+        mode: Octal(0o750),
+        mask: Hex(i64::MIN),
+        flags: vec![Binary(0b1), Binary(0b1000_0000)],
+        quota: Grouped(10_000_000),
+    })?;
+    // any radix reads into any style
+    assert_eq!(from_str::<Hex<u32>>("- 255")?, Hex(255));
+    assert_eq!(from_str::<Grouped<u32>>("- 0b1_0000")?, Grouped(16));
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Enum {
+    Unit,
+    Newtype(i32),
+    Tuple(i32, i32),
+    Struct { field: i32 },
+}
+
+#[test]
+fn variants() -> serde_kdl::Result {
+    roundtrip(Enum::Unit)?;
+    roundtrip(Enum::Newtype(0))?;
+    roundtrip(Enum::Tuple(0, 0))?;
+    roundtrip(Enum::Struct { field: 0 })?;
+    roundtrip(vec![Enum::Unit, Enum::Newtype(1), Enum::Tuple(2, 3)])?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Unit;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Newtype(i32);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Tuple(i32, i32);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Struct {
+    field: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Nested {
+    name: String,
+    tuple: Tuple,
+    inner: Struct,
+    list: Vec<Struct>,
+    variant: Enum,
+    unit: Enum,
+    option: Option<Struct>,
+}
+
+#[test]
+fn structs() -> serde_kdl::Result {
+    roundtrip(Unit)?;
+    roundtrip(Newtype(0))?;
+    roundtrip(Tuple(0, 0))?;
+    roundtrip(Struct { field: 0 })?;
+    roundtrip(Nested {
+        name: "nested".into(),
+        tuple: Tuple(1, 2),
+        inner: Struct { field: 3 },
+        list: vec![Struct { field: 4 }, Struct { field: 5 }],
+        variant: Enum::Struct { field: 6 },
+        unit: Enum::Unit,
+        option: None,
+    })?;
+    Ok(())
+}
+
+#[test]
+fn sequences() -> serde_kdl::Result {
+    roundtrip(Vec::<i32>::new())?;
+    roundtrip(vec![1])?;
+    roundtrip(vec![vec![1, 2, 3]])?;
+    roundtrip(vec![Struct { field: 0 }])?;
+    roundtrip((1, "two".to_string(), Struct { field: 3 }))?;
+    Ok(())
+}
+
+#[test]
+fn hashmaps() -> serde_kdl::Result {
+    roundtrip::<BTreeMap<String, u32>>([("one".into(), 1)].into_iter().collect())?;
+    roundtrip::<BTreeMap<String, u32>>(
+        [("one".into(), 1), ("two".into(), 2)].into_iter().collect(),
+    )?;
+    roundtrip::<BTreeMap<Struct, Tuple>>(
+        [
+            (Struct { field: 1 }, Tuple(2, 3)),
+            (Struct { field: 4 }, Tuple(5, 6)),
+        ]
+        .into_iter()
+        .collect(),
+    )?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Wrappers {
+    option: Option<i32>,
+    unit: Unit,
+    variant: Enum,
+    newtype: Newtype,
+    nested: Newtype2,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Newtype2(Vec<Struct>);
+
+#[test]
+fn wrapper_options() -> serde_kdl::Result {
+    let value = Wrappers {
+        option: Some(1),
+        unit: Unit,
+        variant: Enum::Unit,
+        newtype: Newtype(2),
+        nested: Newtype2(vec![Struct { field: 3 }]),
+    };
+    let ser_opt = ser::Options::new()
+        .option_as_enum(true)
+        .unit_struct_as_tuple(true)
+        .unit_variant_as_tuple(true)
+        .newtype_as_tuple(true);
+    let de_opt = de::Options::new().newtype_as_tuple(true);
+
+    let human = to_string_with_options(&value, ser_opt.clone())?;
+    assert_eq!(
+        from_str_with_options::<Wrappers>(&human, de_opt.clone())?,
+        value,
+        "{}",
+        human
+    );
+    let ugly = to_string_ugly_with_options(&value, ser_opt)?;
+    assert_eq!(
+        from_str_with_options::<Wrappers>(&ugly, de_opt)?,
+        value,
+        "{}",
+        ugly
+    );
+    Ok(())
+}
+
+#[test]
+fn unit_as_tuple() -> serde_kdl::Result {
+    let opt = ser::Options::new()
+        .unit_struct_as_tuple(true)
+        .unit_variant_as_tuple(true);
+    for value in [vec![Enum::Unit], vec![Enum::Unit, Enum::Newtype(0)]] {
+        let human = to_string_with_options(&value, opt.clone())?;
+        assert_eq!(from_str::<Vec<Enum>>(&human)?, value, "{}", human);
+        let ugly = to_string_ugly_with_options(&value, opt.clone())?;
+        assert_eq!(from_str::<Vec<Enum>>(&ugly)?, value, "{}", ugly);
+    }
+    let ugly = to_string_ugly_with_options(&Unit, opt)?;
+    assert_eq!(ugly, "- { - null; }");
+    assert_eq!(from_str::<Unit>(&ugly)?, Unit);
+    assert_eq!(from_str::<Enum>("(Unit)- { - null; }")?, Enum::Unit);
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Patch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    port: Option<Option<u16>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limit: Option<Option<u32>>,
+    unit: Option<()>,
+}
+
+#[test]
+fn nested_options() -> serde_kdl::Result {
+    roundtrip(vec![Some(Some(1)), Some(None), None])?;
+    roundtrip(Some(None::<i32>))?;
+    // absent, null, and a value all read back as written
+    let option_as_enum = ser::Options::new().option_as_enum(true);
+    for unit in [Some(()), None] {
+        let value = Patch {
+            name: None,
+            port: Some(None),
+            limit: Some(Some(10)),
+            unit,
+        };
+        let human = to_string_with_options(&value, option_as_enum.clone())?;
+        assert_eq!(from_str::<Patch>(&human)?, value, "{}", human);
+        let ugly = to_string_ugly_with_options(&value, option_as_enum.clone())?;
+        assert_eq!(from_str::<Patch>(&ugly)?, value, "{}", ugly);
+        roundtrip(value)?;
+    }
+    assert_eq!(
+        from_str::<Patch>("port (Some)null\nunit (Some)null")?,
+        Patch {
+            name: None,
+            port: Some(None),
+            limit: None,
+            unit: Some(()),
+        }
+    );
+
+    // there is no room for a second `(Some)`
+    let deeper = [Some(Some(None::<i32>))];
+    assert!(to_string(&deeper).is_err());
+    assert!(to_string_with_options(&deeper, option_as_enum).is_err());
+    assert!(to_string(&Enum::Newtype(1)).is_ok());
+    let nested = [Some(Enum::Newtype(1))];
+    assert!(to_string_with_options(&nested, ser::Options::new().option_as_enum(true)).is_err());
+    let opt = ser::Options::new()
+        .option_as_enum(true)
+        .newtype_as_tuple(true);
+    let human = to_string_with_options(&nested, opt)?;
+    let de_opt = de::Options::new().newtype_as_tuple(true);
+    assert_eq!(
+        from_str_with_options::<Vec<Option<Enum>>>(&human, de_opt)?,
+        nested
+    );
+    Ok(())
+}
+
+#[test]
+fn handwritten() -> serde_kdl::Result {
+    let nested: Nested = from_str(
+        r#"
+        // comments are ignored
+        name "nested" /* anywhere */
+        tuple 1 2
+        inner field=3
+        list {
+            - field=4
+            /- - field=99
+            - { field 5; }
+        }
+        (Struct)variant field=6
+        unit (Unit)null
+        option null
+        "#,
+    )?;
+    assert_eq!(nested.name, "nested");
+    assert_eq!(nested.list, vec![Struct { field: 4 }, Struct { field: 5 }]);
+
+    let map: BTreeMap<String, u32> = from_str("one 1\n\"two\" 2")?;
+    assert_eq!(map["one"], 1);
+    assert_eq!(map["two"], 2);
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Text {
+    text: String,
+    nested: Vec<Text>,
+}
+
+#[test]
+fn multi_line_strings() -> serde_kdl::Result {
+    for text in [
+        "two\nlines",
+        "\n  leading blank line and indentation\n",
+        "trailing\n\n",
+        "\"\"\" and \\ need a raw string\n\"#",
+        "only whitespace\n  \nlines are escaped",
+        "carriage\r\nreturn",
+        "control\u{1}\ncharacter",
+    ] {
+        roundtrip(text.to_string())?;
+        roundtrip(Text {
+            text: text.into(),
+            nested: vec![Text {
+                text: text.into(),
+                nested: vec![],
+            }],
+        })?;
+        roundtrip(vec![Text {
+            text: text.into(),
+            nested: vec![],
+        }])?;
+    }
+
+    let text: Text =
+        from_str("text \"\"\"\n    one\\s\n\n      two\\\n    \\tthree\r\n    \"\"\"\nnested {}")?;
+    assert_eq!(text.text, "one \n\n  two\tthree");
+    let text: String = from_str("- #\"\"\"\n  raw\\n\n  \"\"\"#")?;
+    assert_eq!(text, "raw\\n");
+    let err = from_str::<String>("- \"\"\"\n  one\n two\n  \"\"\"").unwrap_err();
+    assert_eq!(err.location().unwrap().column, 3);
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Empty {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Collections {
+    list: Option<Vec<i32>>,
+    map: Option<BTreeMap<String, i32>>,
+    empty: Option<Empty>,
+    unit: (),
+}
+
+#[test]
+fn empty_collections() -> serde_kdl::Result {
+    roundtrip(Collections {
+        list: Some(vec![]),
+        map: Some(BTreeMap::new()),
+        empty: Some(Empty {}),
+        unit: (),
+    })?;
+    roundtrip(Collections {
+        list: None,
+        map: None,
+        empty: None,
+        unit: (),
+    })?;
+    roundtrip(vec![Empty {}])?;
+
+    let bare: Collections = from_str("list\nmap\nempty\nunit")?;
+    assert_eq!((bare.list, bare.map, bare.empty), (None, None, None));
+    let empty: Collections = from_str("list {}\nmap {}\nempty {}\nunit null")?;
+    assert_eq!(empty.list, Some(vec![]));
+    assert_eq!(empty.map, Some(BTreeMap::new()));
+    assert_eq!(empty.empty, Some(Empty {}));
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Package {
+    name: String,
+    #[serde(default)]
+    dependency: Vec<Dependency>,
+    #[serde(default)]
+    author: Vec<String>,
+    features: Option<Vec<String>>,
+    matrix: Vec<Vec<i32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Dependency {
+    name: String,
+    version: Option<String>,
+}
+
+#[test]
+fn repeated_seq_fields() -> serde_kdl::Result {
+    let ser_opt = ser::Options::new().repeated_seq_fields(true);
+    let de_opt = de::Options::new().repeated_seq_fields(true);
+    let package = Package {
+        name: "serde-kdl".into(),
+        dependency: vec![
+            Dependency {
+                name: "serde".into(),
+                version: Some("1.0".into()),
+            },
+            Dependency {
+                name: "kdl".into(),
+                version: None,
+            },
+        ],
+        author: vec![],
+        features: Some(vec!["std".into()]),
+        matrix: vec![vec![1, 2], vec![]],
+    };
+    let human = to_string_with_options(&package, ser_opt.clone())?;
+    assert_eq!(
+        from_str_with_options::<Package>(&human, de_opt.clone())?,
+        package,
+        "{}",
+        human
+    );
+    let ugly = to_string_ugly_with_options(&package, ser_opt)?;
+    assert_eq!(
+        from_str_with_options::<Package>(&ugly, de_opt.clone())?,
+        package,
+        "{}",
+        ugly
+    );
+
+    let package: Package = from_str_with_options(
+        r#"
+        name "serde-kdl"
+        dependency name="serde" version="1.0"
+        author "CAD97"
+        dependency name="kdl"
+        matrix 1 2
+        "#,
+        de_opt.clone(),
+    )?;
+    assert_eq!(package.dependency.len(), 2);
+    assert_eq!(package.author, ["CAD97"]);
+    assert_eq!(package.features, None);
+    assert_eq!(package.matrix, [[1, 2]]);
+
+    let err = from_str_with_options::<Package>("name a\nname b\nmatrix 1", de_opt).unwrap_err();
+    assert_eq!(err.location().unwrap().line, 2, "{}", err);
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Listener {
+    name: Arg<String>,
+    tls: Child<bool>,
+    routes: Vec<Route>,
+    port: Prop<u16>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Route {
+    path: Arg<String>,
+    methods: Prop<Vec<String>>,
+    handler: Arg<Newtype>,
+}
+
+#[test]
+fn placement() -> serde_kdl::Result {
+    let listener = Listener {
+        name: Arg("web".into()),
+        tls: Child(true),
+        routes: vec![Route {
+            path: Arg("/".into()),
+            methods: Prop(vec!["GET".into()]),
+            handler: Arg(Newtype(0)),
+        }],
+        port: Prop(80),
+    };
+    roundtrip(vec![listener])?;
+
+    let listener: Listener = from_str(
+        r#"
+        - "web" port=443 {
+            tls #true
+            routes
+        }
+        "#,
+    )?;
+    assert_eq!(*listener.name, "web");
+    assert_eq!(listener.port, Prop(443));
+
+    let opt = de::Options::new().newtype_as_tuple(true);
+    let arg: Arg<Newtype> = from_str_with_options("- 1", opt)?;
+    assert_eq!(arg.into_inner(), Newtype(1));
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Rule {
+    key: u32,
+    value: Action,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Action {
+    command: String,
+    args: Vec<String>,
+    retry: Option<Retry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Retry {
+    attempts: u32,
+}
+
+#[test]
+fn flatten_tail_struct() -> serde_kdl::Result {
+    let ser_opt = ser::Options::new().flatten_tail_struct(true);
+    let de_opt = de::Options::new().flatten_tail_struct(true);
+    let rules = vec![
+        Rule {
+            key: 0,
+            value: Action {
+                command: "echo".into(),
+                args: vec!["hello".into(), "world".into()],
+                retry: Some(Retry { attempts: 3 }),
+            },
+        },
+        Rule {
+            key: 1,
+            value: Action {
+                command: "true".into(),
+                args: vec![],
+                retry: None,
+            },
+        },
+    ];
+    let human = to_string_with_options(&rules, ser_opt.clone())?;
+    assert_eq!(
+        from_str_with_options::<Vec<Rule>>(&human, de_opt.clone())?,
+        rules,
+        "{}",
+        human
+    );
+    let ugly = to_string_ugly_with_options(&rules, ser_opt)?;
+    assert_eq!(
+        from_str_with_options::<Vec<Rule>>(&ugly, de_opt.clone())?,
+        rules,
+        "{}",
+        ugly
+    );
+
+    // the tail field may still be given as a node of its own
+    let rule: Rule = from_str_with_options(
+        r#"
+        - key=0 {
+            value command="echo" {
+                args "hi"
+            }
+        }
+        "#,
+        de_opt.clone(),
+    )?;
+    assert_eq!(rule.value.args, ["hi"]);
+
+    let err = from_str_with_options::<Rule>(
+        "- key=0 command=\"echo\" {\n    value command=\"echo\"\n}",
+        de_opt.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err.location().unwrap().line, 2, "{}", err);
+    let err = from_str_with_options::<Rule>("- key=0 key=1 command=\"echo\"", de_opt).unwrap_err();
+    assert!(err.to_string().contains("more than once"), "{}", err);
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Include {
+    files: Vec<String>,
+    #[serde(default)]
+    optional: bool,
+}
+
+#[test]
+fn leading_seq_as_arguments() -> serde_kdl::Result {
+    let ser_opt = ser::Options::new().leading_seq_as_arguments(true);
+    let de_opt = de::Options::new().leading_seq_as_arguments(true);
+    let includes = vec![
+        Include {
+            files: vec!["a.kdl".into(), "b.kdl".into()],
+            optional: true,
+        },
+        Include {
+            files: vec![],
+            optional: false,
+        },
+    ];
+    let human = to_string_with_options(&includes, ser_opt.clone())?;
+    assert_eq!(
+        from_str_with_options::<Vec<Include>>(&human, de_opt.clone())?,
+        includes,
+        "{}",
+        human
+    );
+    let ugly = to_string_ugly_with_options(&includes, ser_opt)?;
+    assert_eq!(
+        from_str_with_options::<Vec<Include>>(&ugly, de_opt.clone())?,
+        includes,
+        "{}",
+        ugly
+    );
+
+    let include: Include = from_str_with_options(r#"- "a.kdl" "b.kdl" "c.kdl""#, de_opt.clone())?;
+    assert_eq!(include.files, ["a.kdl", "b.kdl", "c.kdl"]);
+
+    // a leading field which isn't a `seq` takes a single argument
+    let err = from_str_with_options::<Struct>("- 0 1", de_opt).unwrap_err();
+    assert_eq!(err.location().unwrap().column, 5, "{}", err);
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Service {
+    name: String,
+    #[serde(flatten)]
+    metadata: Metadata,
+    #[serde(flatten)]
+    limits: Limits,
+    #[serde(flatten)]
+    labels: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Metadata {
+    version: u32,
+    owners: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Limits {
+    memory: Option<u64>,
+    cpus: f64,
+}
+
+#[test]
+fn flatten() -> serde_kdl::Result {
+    let service = Service {
+        name: "web".into(),
+        metadata: Metadata {
+            version: 2,
+            owners: vec!["ops".into()],
+        },
+        limits: Limits {
+            memory: None,
+            cpus: 0.5,
+        },
+        labels: [("tier".to_string(), "frontend".to_string())]
+            .into_iter()
+            .collect(),
+    };
+    roundtrip(vec![service])?;
+
+    let service: Service = from_str(
+        r#"
+        name "api"
+        version 1
+        owners "dev" "ops"
+        memory 512
+        cpus 2.0
+        region "eu"
+        zone "b"
+        "#,
+    )?;
+    assert_eq!(service.metadata.owners, ["dev", "ops"]);
+    assert_eq!(service.limits.memory, Some(512));
+    assert_eq!(service.labels.len(), 2);
+    assert_eq!(service.labels["zone"], "b");
+
+    let service: Service = from_str(
+        r#"
+        - name="api" version=1 memory=null cpus=1.5 region="eu" {
+            owners {
+                - "dev"
+            }
+        }
+        "#,
+    )?;
+    assert_eq!(service.limits.memory, None);
+    assert_eq!(service.labels["region"], "eu");
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type")]
+enum Endpoint {
+    Http { port: u16 },
+    Tls(TlsEndpoint),
+    Closed,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct TlsEndpoint {
+    port: u16,
+    cert: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", content = "value")]
+enum Message {
+    Ping,
+    Text(String),
+    Move { x: i32, y: i32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Plugin {
+    endpoints: Vec<Endpoint>,
+    messages: Vec<Message>,
+}
+
+fn plugin() -> Plugin {
+    Plugin {
+        endpoints: vec![
+            Endpoint::Http { port: 80 },
+            Endpoint::Tls(TlsEndpoint {
+                port: 443,
+                cert: "cert.pem".into(),
+            }),
+            Endpoint::Closed,
+        ],
+        messages: vec![
+            Message::Ping,
+            Message::Text("hi".into()),
+            Message::Move { x: 1, y: -1 },
+        ],
+    }
+}
+
+#[test]
+fn tagged_enums() -> serde_kdl::Result {
+    roundtrip(plugin())?;
+
+    let plugin = plugin();
+    let ser_opt = ser::Options::new().tag_as_type_annotation(Some("type"));
+    let de_opt = de::Options::new().tag_as_type_annotation(Some("type"));
+    let human = to_string_with_options(&plugin, ser_opt.clone())?;
+    assert_eq!(
+        from_str_with_options::<Plugin>(&human, de_opt.clone())?,
+        plugin,
+        "{}",
+        human
+    );
+    let ugly = to_string_ugly_with_options(&plugin, ser_opt)?;
+    assert_eq!(
+        from_str_with_options::<Plugin>(&ugly, de_opt.clone())?,
+        plugin,
+        "{}",
+        ugly
+    );
+
+    let endpoint: Endpoint = from_str_with_options("(Http)- port=80", de_opt.clone())?;
+    assert_eq!(endpoint, Endpoint::Http { port: 80 });
+    let endpoint: Endpoint = from_str(r#"- type="Http" port=80"#)?;
+    assert_eq!(endpoint, Endpoint::Http { port: 80 });
+    let message: Message = from_str(
+        r#"
+        value x=1 y=-1
+        type (Move)null
+        "#,
+    )?;
+    assert_eq!(message, Message::Move { x: 1, y: -1 });
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+enum Setting {
+    Flag(bool),
+    Number(i64),
+    Text(String),
+    Mode(Enum),
+    List(Vec<Setting>),
+    Table(BTreeMap<String, Setting>),
+}
+
+#[test]
+fn self_describing() -> serde_kdl::Result {
+    roundtrip(json!({
+        "name": "serde-kdl",
+        "version": 1,
+        "ratio": 0.5,
+        "private": true,
+        "homepage": null,
+        "keywords": ["kdl", "serde"],
+        "authors": ["CAD97"],
+        "nested": [[1], [], {"x": -1, "y": [{"z": null}]}],
+        "weird key": "-",
+        "-": "dash",
+    }))?;
+    roundtrip(json!([{"a": 1}]))?;
+    roundtrip(json!(["only"]))?;
+
+    let settings: BTreeMap<String, Setting> = [
+        ("verbose", Setting::Flag(true)),
+        ("jobs", Setting::Number(4)),
+        ("mode", Setting::Mode(Enum::Tuple(1, 2))),
+        (
+            "paths",
+            Setting::List(vec![Setting::Text("src".into()), Setting::Number(0)]),
+        ),
+        (
+            "env",
+            Setting::Table([("CI".into(), Setting::Flag(false))].into()),
+        ),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value))
+    .collect();
+    roundtrip(settings)?;
+
+    let value: Value = from_str(
+        r#"
+        title "SiK"
+        point 1 2
+        origin x=0 y=0.5
+        items {
+            - 1
+            - "two"
+        }
+        empty
+        shape (Circle)1.5
+        shapes {
+            (Square)- side=2
+        }
+        "#,
+    )?;
+    assert_eq!(
+        value,
+        json!({
+            "title": "SiK",
+            "point": [1, 2],
+            "origin": {"x": 0, "y": 0.5},
+            "items": [1, "two"],
+            "empty": null,
+            "shape": {"Circle": 1.5},
+            "shapes": [{"Square": {"side": 2}}],
+        })
+    );
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[test]
+fn strict_and_lenient() -> serde_kdl::Result {
+    // base SiK reads the same in every profile
+    let kdl = "- {\n    name \"base\"\n    tuple 1 2\n    inner field=3\n    list {\n        - field=4\n    }\n    (Struct)variant field=5\n    unit (Unit)null\n    option null\n}";
+    let nested = from_str::<Nested>(kdl)?;
+    for opt in [de::Options::strict(), de::Options::lenient()] {
+        assert_eq!(from_str_with_options::<Nested>(kdl, opt)?, nested);
+    }
+
+    /// Read with an extension that the default options accept.
+    fn extension<T>(kdl: &str, value: T) -> serde_kdl::Result
+    where
+        T: DeserializeOwned + PartialEq + Debug,
+    {
+        assert_eq!(from_str::<T>(kdl)?, value, "{}", kdl);
+        let lenient = from_str_with_options::<T>(kdl, de::Options::lenient())?;
+        assert_eq!(lenient, value, "{}", kdl);
+        let err = from_str_with_options::<T>(kdl, de::Options::strict()).unwrap_err();
+        assert!(err.location().is_some(), "{}: {}", kdl, err);
+        Ok(())
+    }
+    extension("- { (Struct)- field=0; }", vec![Struct { field: 0 }])?;
+    extension("- (i32)0", 0)?;
+    extension("- (Some)0", Some(0))?;
+    extension("- { - null; }", Unit)?;
+    extension("- { item 1; item 2; }", vec![1, 2])?;
+    extension("- { - key=1 value=2; }", BTreeMap::from([(1, 2)]))?;
+    extension(
+        "- { one 1; - \"two\" 2; }",
+        BTreeMap::from([("one".to_string(), 1), ("two".to_string(), 2)]),
+    )?;
+
+    // optional type annotations must name the type they are read as
+    let err = from_str::<i32>("- (u8)0").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "type annotation `(u8)` does not match `i32` at line 1 column 3"
+    );
+    let err = from_str::<Vec<Struct>>("- { (Point)- field=0; }").unwrap_err();
+    assert_eq!(err.location().unwrap().column, 5, "{}", err);
+
+    // extensions only the lenient profile accepts
+    let kdl = "- y=2 1";
+    assert!(from_str::<Point>(kdl).is_err());
+    assert_eq!(
+        from_str_with_options::<Point>(kdl, de::Options::lenient())?,
+        Point { x: 1, y: 2 }
+    );
+    let kdl = "- { Unit; Newtype 1; }";
+    assert!(from_str::<Vec<Enum>>(kdl).is_err());
+    assert_eq!(
+        from_str_with_options::<Vec<Enum>>(kdl, de::Options::lenient())?,
+        [Enum::Unit, Enum::Newtype(1)]
+    );
+
+    // individual extensions can be turned off
+    let kdl = "field 0";
+    assert_eq!(from_str::<Struct>(kdl)?, Struct { field: 0 });
+    let opt = de::Options::strict().implied_root(false);
+    assert!(from_str_with_options::<Struct>(kdl, opt).is_err());
+    let opt = de::Options::new().mixed_map_entries(false);
+    let err = from_str_with_options::<BTreeMap<String, u32>>("- { one 1; - \"two\" 2; }", opt)
+        .unwrap_err();
+    assert_eq!(err.location().unwrap().column, 12, "{}", err);
+    Ok(())
+}
+
+/// Read back what both formatters write with the default options.
+fn roundtrip_with<T>(value: T, opt: de::Options) -> serde_kdl::Result
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let human = to_string(&value)?;
+    let read = from_str_with_options::<T>(&human, opt.clone())?;
+    assert_eq!(read, value, "human: {}", human);
+    let ugly = to_string_ugly(&value)?;
+    assert_eq!(
+        from_str_with_options::<T>(&ugly, opt)?,
+        value,
+        "ugly: {}",
+        ugly
+    );
+    Ok(())
+}
+
+#[test]
+fn strict_reads_default_output() -> serde_kdl::Result {
+    let strict = de::Options::strict;
+    roundtrip_with(
+        Nested {
+            name: "nested".into(),
+            tuple: Tuple(1, 2),
+            inner: Struct { field: 3 },
+            list: vec![Struct { field: 4 }],
+            variant: Enum::Struct { field: 6 },
+            unit: Enum::Unit,
+            option: Some(Struct { field: 7 }),
+        },
+        strict(),
+    )?;
+    roundtrip_with(vec![Struct { field: 0 }, Struct { field: 1 }], strict())?;
+    roundtrip_with(vec![Enum::Newtype(0), Enum::Tuple(1, 2)], strict())?;
+    roundtrip_with(Enum::Struct { field: 0 }, strict())?;
+    roundtrip_with(Some(Newtype(0)), strict())?;
+    roundtrip_with(BTreeMap::from([("one".to_string(), 1)]), strict())?;
+    // the key `-` can't name its node, so is written with `key` and `value`
+    roundtrip_with(
+        BTreeMap::from([("-".to_string(), 1), ("a".to_string(), 2)]),
+        strict(),
+    )?;
+
+    // maps with other keys are written as alternative map entries
+    let map = BTreeMap::from([(1, Struct { field: 1 }), (2, Struct { field: 2 })]);
+    assert!(from_str_with_options::<BTreeMap<i32, Struct>>(&to_string(&map)?, strict()).is_err());
+    roundtrip_with(map, strict().alternative_map_entries(true))?;
+    Ok(())
+}
+
+#[test]
+fn warnings() -> serde_kdl::Result {
+    let kdl = r#"
+        /- name "old"
+        name (text)"new"
+        tuple 1 2
+        inner field=3
+        list {
+            /- - field=99
+        }
+        (Struct)variant field=6
+        unit (Unit)null
+        option null
+    "#;
+    let (nested, warnings) = de::from_str_with_warnings::<Nested>(kdl, de::Options::new())?;
+    assert_eq!(nested.name, "new");
+    let warnings: Vec<_> = warnings.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        warnings,
+        [
+            "ignored node commented out with `/-` at line 2 column 9",
+            "ignored node commented out with `/-` at line 7 column 13",
+            "ignored type annotation `(text)` at line 3 column 14",
+        ]
+    );
+
+    let (point, warnings) = de::from_str_with_warnings::<Point>("- y=2 1", de::Options::lenient())?;
+    assert_eq!(point, Point { x: 1, y: 2 });
+    assert_eq!(
+        warnings[0].message,
+        "argument after properties is read as if it came first"
+    );
+    assert_eq!(warnings[0].location.column, 7);
+
+    let (map, warnings) = de::from_str_with_warnings::<BTreeMap<String, u32>>(
+        "one 1\n- \"two\" 2\n- key=\"three\" value=3",
+        de::Options::new(),
+    )?;
+    assert_eq!(map.len(), 3);
+    let lines: Vec<_> = warnings.iter().map(|w| w.location.line).collect();
+    assert_eq!(lines, [2, 3]);
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Config {
+    version: String,
+    port: u16,
+    ratio: f64,
+    enabled: bool,
+    debug: bool,
+}
+
+#[test]
+fn coercion() -> serde_kdl::Result {
+    let kdl = r#"
+        version 1.0
+        port "8080"
+        ratio "0.5"
+        enabled "Yes"
+        debug "off"
+    "#;
+    let config = Config {
+        version: "1.0".into(),
+        port: 8080,
+        ratio: 0.5,
+        enabled: true,
+        debug: false,
+    };
+    assert!(from_str::<Config>(kdl).is_err());
+    let coercion = de::Coercion::new().warn(true);
+    let opt = de::Options::new().coercion(Some(coercion.clone()));
+    let (coerced, warnings) = de::from_str_with_warnings::<Config>(kdl, opt)?;
+    assert_eq!(coerced, config);
+    let warnings: Vec<_> = warnings.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        warnings,
+        [
+            "read number `1.0` as a string at line 2 column 17",
+            "read string `\"8080\"` as a number at line 3 column 14",
+            "read string `\"0.5\"` as a number at line 4 column 15",
+            "read string `\"Yes\"` as `true` at line 5 column 17",
+            "read string `\"off\"` as `false` at line 6 column 15",
+        ]
+    );
+
+    // the literal text is kept, and only the spellings given are bools
+    let opt = de::Options::new().coercion(Some(de::Coercion::new().true_strings(["y"])));
+    assert_eq!(
+        from_str_with_options::<Vec<String>>("- 0x1F\n- 1_000", opt.clone())?,
+        ["0x1F", "1_000"]
+    );
+    assert!(from_str_with_options::<bool>("- \"y\"", opt.clone())?);
+    assert!(from_str_with_options::<bool>("- \"yes\"", opt.clone()).is_err());
+    assert!(from_str_with_options::<u8>("- \"256\"", opt.clone()).is_err());
+    assert!(from_str_with_options::<u8>("- \"8 bits\"", opt.clone()).is_err());
+    // a self-describing value is read as written
+    assert_eq!(
+        from_str_with_options::<Value>("- \"8080\"", opt)?,
+        json!("8080")
+    );
+
+    let opt = de::Options::new().coercion(Some(coercion.strings_as_numbers(false)));
+    assert!(from_str_with_options::<u16>("- \"8080\"", opt).is_err());
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Server {
+    host: Spanned<String>,
+    port: Spanned<u32>,
+    routes: Vec<Spanned<Path>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Path {
+    path: String,
+}
+
+#[test]
+fn spanned() -> serde_kdl::Result {
+    let kdl =
+        "host \"example.com\"\nport 99999\nroutes {\n    - path=\"/\"\n    - path=\"/api\"\n}\n";
+    let server: Server = from_str(kdl)?;
+    assert_eq!(&kdl[server.host.span()], "host \"example.com\"");
+    assert_eq!(&kdl[server.port.span()], "port 99999");
+    let start = server.port.start();
+    assert_eq!((start.line, start.column), (2, 1));
+    let route = &server.routes[1];
+    assert_eq!(route.path, "/api");
+    assert_eq!(&kdl[route.span()], "- path=\"/api\"");
+    assert_eq!((route.start().line, route.end().column), (5, 18));
+
+    // arguments and properties span just their entry
+    let server: Server = from_str("- host=\"localhost\" port=8080 { routes {}; }")?;
+    assert_eq!(server.host.span(), 2..18);
+    assert_eq!(server.port.start().column, 20);
+
+    // spans are transparent when serializing
+    assert_eq!(from_str::<Server>(&to_string(&server)?)?, server);
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Host {
+    name: String,
+    #[serde(flatten)]
+    rest: Document,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Plugins {
+    enabled: bool,
+    config: Document,
+}
+
+#[test]
+fn document() -> serde_kdl::Result {
+    let kdl = r#"
+        name "core"
+        (v2)cache ratio=2.50 {
+            path "/tmp" mode=755
+            - "k" null
+        }
+        debug
+        "#;
+    let host: Host = from_str(kdl)?;
+    assert_eq!(host.name, "core");
+    let nodes = &host.rest.nodes;
+    assert_eq!(nodes.len(), 2);
+    assert_eq!(nodes[0].ty.as_deref(), Some("v2"));
+    assert_eq!(nodes[0].entries[0].name.as_deref(), Some("ratio"));
+    assert_eq!(
+        nodes[0].entries[0].value,
+        serde_kdl::document::Value::Number("2.50".into())
+    );
+    assert_eq!(nodes[0].children.as_ref().unwrap().len(), 2);
+    assert_eq!(
+        (nodes[1].name.as_str(), nodes[1].children.is_none()),
+        ("debug", true)
+    );
+    assert_eq!(from_str::<Host>(&to_string(&host)?)?, host);
+
+    // unclaimed arguments and properties of the struct's own node
+    let hosts: BTreeMap<String, Host> =
+        from_str(r#"primary "a" 1 name="core" extra=#true { more; }"#)?;
+    let host = &hosts["primary"];
+    assert_eq!(host.rest.entries.len(), 3);
+    assert_eq!(host.rest.entries[2].name.as_deref(), Some("extra"));
+    assert_eq!(host.rest.nodes[0].name, "more");
+    assert_eq!(
+        from_str::<BTreeMap<String, Host>>(&to_string(&hosts)?)?,
+        hosts
+    );
+
+    // a whole node, and a whole document
+    let plugins: Plugins = from_str(
+        "enabled #true
+config 1 x=2 { (t)y 3; }",
+    )?;
+    assert_eq!(plugins.config.entries.len(), 2);
+    assert_eq!(plugins.config.nodes[0].ty.as_deref(), Some("t"));
+    assert_eq!(from_str::<Plugins>(&to_string(&plugins)?)?, plugins);
+    let document: Document = from_str(kdl)?;
+    assert_eq!(document.nodes.len(), 3);
+    assert_eq!(from_str::<Document>(&to_string(&document)?)?, document);
+
+    // a number is only written back if it is one
+    let mut document = Document::new();
+    document.entries.push(serde_kdl::document::Entry {
+        name: None,
+        ty: None,
+        value: serde_kdl::document::Value::Number("1e".into()),
+    });
+    assert!(to_string(&document).is_err());
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Gateway {
+    port: u16,
+    max_connections: u32,
+    mode: Option<Enum>,
+}
+
+#[test]
+fn unknown_names() -> serde_kdl::Result {
+    roundtrip(Gateway {
+        port: 80,
+        max_connections: 10,
+        mode: Some(Enum::Tuple(1, 2)),
+    })?;
+
+    let message = |kdl| from_str::<Gateway>(kdl).unwrap_err().to_string();
+    assert_eq!(
+        message("port 80\nmax-conections 10"),
+        "unknown child node `max-conections`, did you mean `max_connections`? at line 2 column 1"
+    );
+    assert_eq!(
+        message("- max_connections=1 prot=80"),
+        "unknown property `prot`, did you mean `port`? at line 1 column 21"
+    );
+    assert_eq!(
+        message("port 80\nmax_connections 1\nhost \"localhost\""),
+        "unknown child node `host`, expected one of `port`, `max_connections`, `mode` \
+         at line 3 column 1"
+    );
+    assert_eq!(
+        message("port 80\nmax_connections 1\n(Tupel)mode 1 2"),
+        "unknown variant `Tupel`, did you mean `Tuple`? at line 3 column 1"
+    );
+    Ok(())
+}
+
+#[test]
+fn reader() -> serde_kdl::Result {
+    let kdl = "// header\n(t)node 1 /* inline */ key=#true /-skip {\n    child; /-gone\n}\n";
+    let events = Reader::new(kdl).collect::<serde_kdl::Result<Vec<_>>>()?;
+    let summary: Vec<String> = (events.iter())
+        .map(|event| match event {
+            Event::StartNode { ty, name, .. } => format!("start {:?} {}", ty.as_deref(), name),
+            Event::Argument { value, .. } => format!("argument {:?}", value),
+            Event::Property { name, value, .. } => format!("property {} {:?}", name, value),
+            Event::StartChildren { .. } => "{".into(),
+            Event::EndChildren { .. } => "}".into(),
+            Event::EndNode { .. } => "end".into(),
+            Event::Comment { text, .. } => format!("comment {}", text),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            "comment // header",
+            "start Some(\"t\") node",
+            "argument Number(\"1\")",
+            "comment /* inline */",
+            "property key Bool(true)",
+            "comment /-skip",
+            "{",
+            "start None child",
+            "end",
+            "comment /-gone",
+            "}",
+            "end",
+        ]
+    );
+    let Event::Argument { value, span, .. } = &events[2] else {
+        unreachable!()
+    };
+    assert_eq!(
+        (value, &kdl[span.start..span.end]),
+        (&Literal::Number("1"), "1")
+    );
+
+    // the iterator stops at the first error
+    let mut reader = Reader::new("a {");
+    assert_eq!(reader.by_ref().filter(Result::is_ok).count(), 2);
+    assert!(reader.next().is_none());
+    Ok(())
+}
+
+#[test]
+fn stream() -> serde_kdl::Result {
+    let routes = || {
+        (0..3).map(|i| Path {
+            path: format!("/{}", i),
+        })
+    };
+    let mut log = Vec::new();
+    let mut stream = StreamSerializer::new(&mut log);
+    routes().try_for_each(|route| stream.serialize(&route))?;
+    let mut stream = StreamSerializer::ugly(&mut log);
+    routes().try_for_each(|route| stream.serialize(&route))?;
+    stream.serialize(&Tuple(1, 2))?;
+
+    // the whole log is a seq, too
+    let all: Vec<Value> = from_str(std::str::from_utf8(&log).unwrap())?;
+    assert_eq!(all.len(), 7);
+
+    // one byte at a time, to split every node
+    struct Trickle<'a>(&'a [u8]);
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let (byte, rest) = match self.0 {
+                [] => return Ok(0),
+                [byte, rest @ ..] => (byte, rest),
+            };
+            buf[0] = *byte;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+    let mut stream = StreamDeserializer::<_, Spanned<Path>>::new(Trickle(&log));
+    for expected in routes().chain(routes()) {
+        let route = stream.next().unwrap()?;
+        assert_eq!(*route, expected);
+        assert_eq!(stream.byte_offset(), route.span().end + 1);
+    }
+    // a node which isn't a `T` doesn't stop the stream
+    let err = stream.next().unwrap().unwrap_err();
+    assert_eq!(err.location().unwrap().line, 7, "{}", err);
+    assert!(stream.next().is_none());
+
+    let mut stream = StreamDeserializer::<_, u32>::new("- 1; /-- 2\n// end\n- {".as_bytes());
+    assert_eq!(stream.next().unwrap()?, 1);
+    let err = stream.next().unwrap().unwrap_err();
+    assert!(matches!(err, serde_kdl::Error::Syntax { .. }), "{}", err);
+    assert!(stream.next().is_none());
+
+    // a syntax error is reported without waiting for the rest of the input
+    let endless = "- 1\n- 2 }\n".as_bytes().chain(io::repeat(b'\n'));
+    let mut stream = StreamDeserializer::<_, u32>::new(endless);
+    assert_eq!(stream.next().unwrap()?, 1);
+    assert_eq!(stream.next().unwrap()?, 2);
+    let err = stream.next().unwrap().unwrap_err();
+    let location = err.location().unwrap();
+    assert_eq!((location.line, location.column), (2, 5), "{}", err);
+    assert!(stream.next().is_none());
+    Ok(())
+}
+
+#[test]
+fn limits() {
+    // the limit exceeded, and where
+    let exceeds = |kdl: &str, limits: read::Limits| {
+        let opt = de::Options::new().limits(limits);
+        match from_str_with_options::<Value>(kdl, opt) {
+            Err(serde_kdl::Error::Limit {
+                limit,
+                location: Some(location),
+                ..
+            }) => (limit, location.line, location.column),
+            result => panic!("{:?}", result),
+        }
+    };
+    let deep = "a {\n".repeat(200) + &"}\n".repeat(200);
+    assert_eq!(exceeds(&deep, read::Limits::new()), (Limit::Depth, 129, 3));
+    assert!(
+        from_str_with_options::<Value>(&deep, de::Options::new().limits(read::Limits::none()))
+            .is_ok()
+    );
+    let slashdashed = "a; /-b { c; }; d";
+    assert_eq!(
+        exceeds(slashdashed, read::Limits::new().nodes(3)),
+        (Limit::Nodes, 1, 16)
+    );
+    assert_eq!(
+        exceeds(slashdashed, read::Limits::new().depth(0)),
+        (Limit::Depth, 1, 8)
+    );
+    // slashdashes one after another don't nest
+    let slashdashed = "/- ".repeat(200_000) + "node";
+    let reader = Reader::with_limits(&slashdashed, read::Limits::new().depth(16));
+    assert_eq!(
+        reader
+            .map(|event| event.map(drop))
+            .collect::<Vec<_>>()
+            .len(),
+        1
+    );
+    assert_eq!(
+        exceeds("a 1 /-2 x=3 4", read::Limits::new().entries(2)),
+        (Limit::Entries, 1, 13)
+    );
+    let strings = read::Limits::new().string_length(4);
+    assert_eq!(
+        exceeds("a \"four\" \"f\\u{3bf}ur\"", strings),
+        (Limit::StringLength, 1, 10)
+    );
+    assert_eq!(exceeds("\"named\" 1", strings), (Limit::StringLength, 1, 1));
+    assert_eq!(exceeds("named 1", strings), (Limit::StringLength, 1, 1));
+    assert_eq!(exceeds("a names=1", strings), (Limit::StringLength, 1, 3));
+    assert_eq!(exceeds("a (types)1", strings), (Limit::StringLength, 1, 4));
+    assert_eq!(
+        exceeds("- four fives", strings),
+        (Limit::StringLength, 1, 8)
+    );
+    let hashes = read::Limits::new().raw_string_hashes(1);
+    assert_eq!(
+        exceeds("a #\"x\"# ##\"y\"##", hashes),
+        (Limit::RawStringHashes, 1, 9)
+    );
+    let numbers = read::Limits::new().number_length(4);
+    assert_eq!(
+        exceeds("a 1.25 -1.25", numbers),
+        (Limit::NumberLength, 1, 8)
+    );
+
+    // a stream stops at the first node over a limit
+    let opt = de::Options::new().limits(read::Limits::new().depth(2));
+    let kdl = "- 1\n- { - { - { - 2; }; }; }\n- 3\n";
+    let mut stream = StreamDeserializer::<_, Value>::new_with_options(kdl.as_bytes(), opt);
+    assert_eq!(stream.next().unwrap().unwrap(), json!(1));
+    let err = stream.next().unwrap().unwrap_err();
+    assert_eq!(err.location().unwrap().line, 2, "{}", err);
+    assert!(stream.next().is_none());
+
+    // and at a node which doesn't fit in its buffer
+    let opt = de::Options::new().limits(read::Limits::new().buffer_length(64));
+    let endless = "- 1\n- \"".as_bytes().chain(io::repeat(b'x'));
+    let mut stream = StreamDeserializer::<_, Value>::new_with_options(endless, opt);
+    assert_eq!(stream.next().unwrap().unwrap(), json!(1));
+    match stream.next().unwrap() {
+        Err(serde_kdl::Error::Limit {
+            limit: Limit::BufferLength,
+            max: 64,
+            location: Some(location),
+        }) => assert_eq!((location.offset, location.line), (68, 2)),
+        result => panic!("{:?}", result),
+    }
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn errors() {
+    let err = from_str::<Struct>("field \"zero\"").unwrap_err();
+    let location = err.location().unwrap();
+    assert_eq!((location.line, location.column), (1, 7));
+
+    let err = from_str::<Struct>("\nfield 0 {").unwrap_err();
+    assert!(matches!(err, serde_kdl::Error::Syntax { .. }), "{}", err);
+
+    let err = from_str::<Tuple>("- 1 x=2").unwrap_err();
+    assert_eq!(err.location().unwrap().column, 5);
+}
@@ -1,37 +1,69 @@
 use {
-    serde::Serialize,
+    serde::{ser::SerializeMap, Serialize, Serializer},
     serde_bytes::Bytes,
-    serde_kdl::ser::{to_string, to_string_ugly},
-    std::collections::BTreeMap,
+    serde_kdl::{
+        error::Limit,
+        ser::{
+            to_string, to_string_ugly, to_string_ugly_with_options, to_string_with_options,
+            Options, Version,
+        },
+        Arg, Binary, Child, Grouped, Hex, Octal, Prop,
+    },
+    std::{cell::Cell, collections::BTreeMap},
 };
 
 #[test]
 fn ugly_primitives() -> serde_kdl::Result {
-    insta::assert_display_snapshot!("ugly bool", to_string_ugly(&true)?);
-    insta::assert_display_snapshot!("ugly u32", to_string_ugly(&0u32)?);
-    insta::assert_display_snapshot!("ugly i32", to_string_ugly(&0i32)?);
-    insta::assert_display_snapshot!("ugly f32", to_string_ugly(&0f32)?);
-    insta::assert_display_snapshot!("ugly string", to_string_ugly("Hello")?);
-    insta::assert_display_snapshot!("ugly bytes", to_string_ugly(Bytes::new(b"KDL"))?);
-    insta::assert_display_snapshot!("ugly char", to_string_ugly(&'🦀')?);
-    insta::assert_display_snapshot!("ugly unit", to_string_ugly(&())?);
-    insta::assert_display_snapshot!("ugly some", to_string_ugly(&Some(0))?);
-    insta::assert_display_snapshot!("ugly none", to_string_ugly(&None::<i32>)?);
+    insta::assert_snapshot!("ugly bool", to_string_ugly(&true)?);
+    insta::assert_snapshot!("ugly u32", to_string_ugly(&0u32)?);
+    insta::assert_snapshot!("ugly i32", to_string_ugly(&0i32)?);
+    insta::assert_snapshot!("ugly f32", to_string_ugly(&0f32)?);
+    insta::assert_snapshot!("ugly string", to_string_ugly("Hello")?);
+    insta::assert_snapshot!("ugly bytes", to_string_ugly(Bytes::new(b"KDL"))?);
+    insta::assert_snapshot!("ugly char", to_string_ugly(&'🦀')?);
+    insta::assert_snapshot!("ugly unit", to_string_ugly(&())?);
+    insta::assert_snapshot!("ugly some", to_string_ugly(&Some(0))?);
+    insta::assert_snapshot!("ugly none", to_string_ugly(&None::<i32>)?);
     Ok(())
 }
 
 #[test]
 fn human_primitives() -> serde_kdl::Result {
-    insta::assert_display_snapshot!("human bool", to_string(&true)?);
-    insta::assert_display_snapshot!("human u32", to_string(&0u32)?);
-    insta::assert_display_snapshot!("human i32", to_string(&0i32)?);
-    insta::assert_display_snapshot!("human f32", to_string(&0f32)?);
-    insta::assert_display_snapshot!("human string", to_string("Hello")?);
-    insta::assert_display_snapshot!("human bytes", to_string(Bytes::new(b"KDL"))?);
-    insta::assert_display_snapshot!("human char", to_string(&'🦀')?);
-    insta::assert_display_snapshot!("human unit", to_string(&())?);
-    insta::assert_display_snapshot!("human some", to_string(&Some(0))?);
-    insta::assert_display_snapshot!("human none", to_string(&None::<i32>)?);
+    insta::assert_snapshot!("human bool", to_string(&true)?);
+    insta::assert_snapshot!("human u32", to_string(&0u32)?);
+    insta::assert_snapshot!("human i32", to_string(&0i32)?);
+    insta::assert_snapshot!("human f32", to_string(&0f32)?);
+    insta::assert_snapshot!("human string", to_string("Hello")?);
+    insta::assert_snapshot!("human bytes", to_string(Bytes::new(b"KDL"))?);
+    insta::assert_snapshot!("human char", to_string(&'🦀')?);
+    insta::assert_snapshot!("human unit", to_string(&())?);
+    insta::assert_snapshot!("human some", to_string(&Some(0))?);
+    insta::assert_snapshot!("human none", to_string(&None::<i32>)?);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Keywords {
+    yes: bool,
+    unit: (),
+    infinity: f64,
+    quoted: &'static str,
+    #[serde(rename = "needs quotes")]
+    needs_quotes: i32,
+}
+
+#[test]
+fn v2() -> serde_kdl::Result {
+    let value = Keywords {
+        yes: true,
+        unit: (),
+        infinity: f64::NEG_INFINITY,
+        quoted: r#"say "hi""#,
+        needs_quotes: 1,
+    };
+    let opt = Options::new().version(Version::V2);
+    insta::assert_snapshot!("human v2", to_string_with_options(&value, opt.clone())?);
+    insta::assert_snapshot!("ugly v2", to_string_ugly_with_options(&value, opt)?);
     Ok(())
 }
 
@@ -45,10 +77,10 @@ enum Enum {
 
 #[test]
 fn ugly_variants() -> serde_kdl::Result {
-    insta::assert_display_snapshot!("ugly unit variant", to_string_ugly(&Enum::Unit)?);
-    insta::assert_display_snapshot!("ugly newtype variant", to_string_ugly(&Enum::Newtype(0))?);
-    insta::assert_display_snapshot!("ugly tuple variant", to_string_ugly(&Enum::Tuple(0, 0))?);
-    insta::assert_display_snapshot!(
+    insta::assert_snapshot!("ugly unit variant", to_string_ugly(&Enum::Unit)?);
+    insta::assert_snapshot!("ugly newtype variant", to_string_ugly(&Enum::Newtype(0))?);
+    insta::assert_snapshot!("ugly tuple variant", to_string_ugly(&Enum::Tuple(0, 0))?);
+    insta::assert_snapshot!(
         "ugly struct variant",
         to_string_ugly(&Enum::Struct { field: 0 })?
     );
@@ -57,10 +89,10 @@ fn ugly_variants() -> serde_kdl::Result {
 
 #[test]
 fn human_variants() -> serde_kdl::Result {
-    insta::assert_display_snapshot!("human unit variant", to_string(&Enum::Unit)?);
-    insta::assert_display_snapshot!("human newtype variant", to_string(&Enum::Newtype(0))?);
-    insta::assert_display_snapshot!("human tuple variant", to_string(&Enum::Tuple(0, 0))?);
-    insta::assert_display_snapshot!(
+    insta::assert_snapshot!("human unit variant", to_string(&Enum::Unit)?);
+    insta::assert_snapshot!("human newtype variant", to_string(&Enum::Newtype(0))?);
+    insta::assert_snapshot!("human tuple variant", to_string(&Enum::Tuple(0, 0))?);
+    insta::assert_snapshot!(
         "human struct variant",
         to_string(&Enum::Struct { field: 0 })?
     );
@@ -83,19 +115,19 @@ struct Struct {
 
 #[test]
 fn ugly_structs() -> serde_kdl::Result {
-    insta::assert_display_snapshot!("ugly unit struct", to_string_ugly(&Unit)?);
-    insta::assert_display_snapshot!("ugly newtype struct", to_string_ugly(&Newtype(0))?);
-    insta::assert_display_snapshot!("ugly tuple struct", to_string_ugly(&Tuple(0, 0))?);
-    insta::assert_display_snapshot!("ugly struct", to_string_ugly(&Struct { field: 0 })?);
+    insta::assert_snapshot!("ugly unit struct", to_string_ugly(&Unit)?);
+    insta::assert_snapshot!("ugly newtype struct", to_string_ugly(&Newtype(0))?);
+    insta::assert_snapshot!("ugly tuple struct", to_string_ugly(&Tuple(0, 0))?);
+    insta::assert_snapshot!("ugly struct", to_string_ugly(&Struct { field: 0 })?);
     Ok(())
 }
 
 #[test]
 fn human_structs() -> serde_kdl::Result {
-    insta::assert_display_snapshot!("human unit struct", to_string(&Unit)?);
-    insta::assert_display_snapshot!("human newtype struct", to_string(&Newtype(0))?);
-    insta::assert_display_snapshot!("human tuple struct", to_string(&Tuple(0, 0))?);
-    insta::assert_display_snapshot!("human struct", to_string(&Struct { field: 0 })?);
+    insta::assert_snapshot!("human unit struct", to_string(&Unit)?);
+    insta::assert_snapshot!("human newtype struct", to_string(&Newtype(0))?);
+    insta::assert_snapshot!("human tuple struct", to_string(&Tuple(0, 0))?);
+    insta::assert_snapshot!("human struct", to_string(&Struct { field: 0 })?);
     Ok(())
 }
 
@@ -104,11 +136,11 @@ type ObjectMap = BTreeMap<Struct, Tuple>;
 
 #[test]
 fn ugly_hashmaps() -> serde_kdl::Result {
-    insta::assert_display_snapshot!(
+    insta::assert_snapshot!(
         "ugly string map",
         to_string_ugly::<StringMap>(&[("one", 1), ("two", 2)].into_iter().collect())?
     );
-    insta::assert_display_snapshot!(
+    insta::assert_snapshot!(
         "ugly object map",
         to_string_ugly::<ObjectMap>(
             &[
@@ -124,11 +156,11 @@ fn ugly_hashmaps() -> serde_kdl::Result {
 
 #[test]
 fn human_hashmaps() -> serde_kdl::Result {
-    insta::assert_display_snapshot!(
+    insta::assert_snapshot!(
         "human string map",
         to_string::<StringMap>(&[("one", 1), ("two", 2)].into_iter().collect())?
     );
-    insta::assert_display_snapshot!(
+    insta::assert_snapshot!(
         "human object map",
         to_string::<ObjectMap>(
             &[
@@ -141,3 +173,476 @@ fn human_hashmaps() -> serde_kdl::Result {
     );
     Ok(())
 }
+
+#[test]
+fn human_options() -> serde_kdl::Result {
+    let opt = Options::new().option_as_enum(true).newtype_as_tuple(true);
+    insta::assert_snapshot!(
+        "human option as enum",
+        to_string_with_options(&[Some(0), None], opt.clone())?
+    );
+    insta::assert_snapshot!(
+        "human newtype as tuple",
+        to_string_with_options(&Newtype(0), opt)?
+    );
+    let nested = [Some(Some(0)), Some(None), None];
+    insta::assert_snapshot!("human nested options", to_string(&nested)?);
+    insta::assert_snapshot!(
+        "human nested options as enum",
+        to_string_with_options(&nested, Options::new().option_as_enum(true))?
+    );
+    let opt = Options::new()
+        .unit_struct_as_tuple(true)
+        .unit_variant_as_tuple(true);
+    insta::assert_snapshot!(
+        "ugly unit as tuple",
+        to_string_ugly_with_options(&(Unit, Enum::Unit), opt)?
+    );
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Script {
+    name: &'static str,
+    inner: Inner,
+}
+
+#[derive(Serialize)]
+struct Inner {
+    sql: &'static str,
+}
+
+#[test]
+fn multi_line_strings() -> serde_kdl::Result {
+    let script = Script {
+        name: "query",
+        inner: Inner {
+            sql: "SELECT *\nFROM \"table\"\n\nWHERE id = 1\n",
+        },
+    };
+    let opt = Options::new().version(Version::V2);
+    insta::assert_snapshot!(
+        "human multi-line string",
+        to_string_with_options(&script, opt.clone())?
+    );
+    insta::assert_snapshot!("ugly multi-line string", to_string_ugly(&script)?);
+    insta::assert_snapshot!(
+        "human multi-line raw string",
+        to_string_with_options("C:\\Users\\\n\"\"\"quoted\"\"\"", opt)?
+    );
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Empty {}
+
+#[derive(Serialize)]
+struct Collections {
+    authors: Vec<i32>,
+    map: StringMap,
+    empty: Empty,
+    nested: Vec<Vec<i32>>,
+}
+
+#[test]
+fn human_empty_collections() -> serde_kdl::Result {
+    insta::assert_snapshot!(
+        "human empty collections",
+        to_string(&Collections {
+            authors: vec![],
+            map: BTreeMap::new(),
+            empty: Empty {},
+            nested: vec![vec![]],
+        })?
+    );
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Package {
+    name: &'static str,
+    dependency: Vec<Dependency>,
+    author: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct Dependency {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[test]
+fn repeated_seq_fields() -> serde_kdl::Result {
+    let package = Package {
+        name: "serde-kdl",
+        dependency: vec![
+            Dependency {
+                name: "serde",
+                version: "1.0",
+            },
+            Dependency {
+                name: "kdl",
+                version: "4.0",
+            },
+        ],
+        author: vec!["CAD97"],
+    };
+    let opt = Options::new().repeated_seq_fields(true);
+    insta::assert_snapshot!(
+        "human repeated seq fields",
+        to_string_with_options(&package, opt.clone())?
+    );
+    insta::assert_snapshot!(
+        "ugly repeated seq fields",
+        to_string_ugly_with_options(&package, opt)?
+    );
+    Ok(())
+}
+
+/// Counts how many times its value is serialized.
+struct Counted<'a, T>(&'a Cell<usize>, T);
+
+impl<T: Serialize> Serialize for Counted<'_, T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.set(self.0.get() + 1);
+        self.1.serialize(serializer)
+    }
+}
+
+#[derive(Serialize)]
+struct CountedFields<'a> {
+    files: Counted<'a, Vec<&'static str>>,
+    title: Counted<'a, Option<&'static str>>,
+    author: Counted<'a, Dependency>,
+    dependency: Counted<'a, Vec<Dependency>>,
+}
+
+#[test]
+fn fields_serialize_once() -> serde_kdl::Result {
+    let count = Cell::new(0);
+    let fields = CountedFields {
+        files: Counted(&count, vec!["a.kdl"]),
+        title: Counted(&count, Some("serde-kdl")),
+        author: Counted(
+            &count,
+            Dependency {
+                name: "CAD97",
+                version: "1",
+            },
+        ),
+        dependency: Counted(&count, vec![]),
+    };
+    let opt = Options::new()
+        .repeated_seq_fields(true)
+        .leading_seq_as_arguments(true)
+        .flatten_tail_struct(true);
+    to_string_with_options(&fields, opt)?;
+    assert_eq!(count.get(), 4);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Server {
+    listener: Vec<Listener>,
+}
+
+#[derive(Serialize)]
+struct Listener {
+    name: Arg<&'static str>,
+    protocol: Arg<&'static str>,
+    tls: Child<bool>,
+    routes: Vec<Route>,
+    port: Prop<u16>,
+}
+
+#[derive(Serialize)]
+struct Route {
+    path: Arg<&'static str>,
+    methods: Prop<&'static str>,
+    // not leading, so not an argument
+    handler: Arg<&'static str>,
+}
+
+#[test]
+fn placement() -> serde_kdl::Result {
+    let server = Server {
+        listener: vec![Listener {
+            name: Arg("web"),
+            protocol: Arg("http"),
+            tls: Child(false),
+            routes: vec![Route {
+                path: Arg("/"),
+                methods: Prop("GET"),
+                handler: Arg("index"),
+            }],
+            port: Prop(80),
+        }],
+    };
+    insta::assert_snapshot!("human placement", to_string(&server)?);
+    insta::assert_snapshot!(
+        "human placement repeated",
+        to_string_with_options(&server, Options::new().repeated_seq_fields(true))?
+    );
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Rule<T> {
+    key: u32,
+    value: T,
+}
+
+#[derive(Serialize)]
+struct Action {
+    command: &'static str,
+    args: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct Clash {
+    key: u32,
+}
+
+#[test]
+fn flatten_tail_struct() -> serde_kdl::Result {
+    let rules = [
+        Rule {
+            key: 0,
+            value: Action {
+                command: "echo",
+                args: vec!["hello", "world"],
+            },
+        },
+        Rule {
+            key: 1,
+            value: Action {
+                command: "true",
+                args: vec![],
+            },
+        },
+    ];
+    let opt = Options::new().flatten_tail_struct(true);
+    insta::assert_snapshot!(
+        "human flatten tail struct",
+        to_string_with_options(&rules, opt.clone())?
+    );
+    insta::assert_snapshot!(
+        "ugly flatten tail struct",
+        to_string_ugly_with_options(&rules, opt.clone())?
+    );
+    let clash = Rule {
+        key: 0,
+        value: Clash { key: 1 },
+    };
+    let err = to_string_with_options(&clash, opt).unwrap_err();
+    insta::assert_snapshot!("flatten tail struct clash", err);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Include {
+    files: Vec<&'static str>,
+    optional: bool,
+}
+
+#[test]
+fn leading_seq_as_arguments() -> serde_kdl::Result {
+    let opt = Options::new().leading_seq_as_arguments(true);
+    let includes = vec![
+        Include {
+            files: vec!["a.kdl", "b.kdl"],
+            optional: true,
+        },
+        Include {
+            files: vec![],
+            optional: false,
+        },
+    ];
+    insta::assert_snapshot!(
+        "human leading seq as arguments",
+        to_string_with_options(&includes, opt.clone())?
+    );
+    let err = to_string_with_options(
+        &[Matrix {
+            rows: vec![vec![1]],
+        }],
+        opt,
+    )
+    .unwrap_err();
+    insta::assert_snapshot!("leading seq of compound as arguments", err);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Matrix {
+    rows: Vec<Vec<i32>>,
+}
+
+#[derive(Serialize)]
+struct Service {
+    name: &'static str,
+    #[serde(flatten)]
+    metadata: Metadata,
+    #[serde(flatten)]
+    labels: StringMap,
+}
+
+#[derive(Serialize)]
+struct Metadata {
+    version: u32,
+    owners: Vec<&'static str>,
+}
+
+#[test]
+fn flatten() -> serde_kdl::Result {
+    let services = [
+        Service {
+            name: "web",
+            metadata: Metadata {
+                version: 2,
+                owners: vec!["dev", "ops"],
+            },
+            labels: [("replicas", 3), ("weird key", 1)].into_iter().collect(),
+        },
+        Service {
+            name: "db",
+            metadata: Metadata {
+                version: 1,
+                owners: vec!["ops"],
+            },
+            labels: StringMap::new(),
+        },
+    ];
+    insta::assert_snapshot!("human flatten", to_string(&services)?);
+    insta::assert_snapshot!("ugly flatten", to_string_ugly(&services)?);
+
+    // a map of unknown length which isn't from `#[serde(flatten)]`
+    let unsized_map = Unsized {
+        map: UnsizedMap(&[("b", 2), ("a", 1)]),
+    };
+    insta::assert_snapshot!("human unsized map", to_string(&unsized_map)?);
+    Ok(())
+}
+
+/// A map serialized without its length, as from an iterator.
+struct UnsizedMap(&'static [(&'static str, i32)]);
+
+impl Serialize for UnsizedMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (key, value) in self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Serialize)]
+struct Unsized {
+    map: UnsizedMap,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum Plugin {
+    Http { port: u16 },
+    Static { root: &'static str, index: bool },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", content = "value")]
+enum Event {
+    Reload,
+    Log(&'static str),
+}
+
+#[derive(Serialize)]
+struct Plugins {
+    listener: Plugin,
+    files: Plugin,
+    events: Vec<Event>,
+}
+
+#[test]
+fn tagged_enums() -> serde_kdl::Result {
+    let plugins = Plugins {
+        listener: Plugin::Http { port: 80 },
+        files: Plugin::Static {
+            root: "/srv",
+            index: true,
+        },
+        events: vec![Event::Reload, Event::Log("started")],
+    };
+    insta::assert_snapshot!("human tagged enums", to_string(&plugins)?);
+    let opt = Options::new().tag_as_type_annotation(Some("type"));
+    insta::assert_snapshot!(
+        "human tag as type annotation",
+        to_string_with_options(&plugins, opt.clone())?
+    );
+    insta::assert_snapshot!(
+        "ugly tag as type annotation",
+        to_string_ugly_with_options(&plugins, opt)?
+    );
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Styles {
+    mode: Octal<u32>,
+    mask: Hex<i16>,
+    bits: Vec<Binary<u8>>,
+    total: Grouped<i64>,
+    name: Hex<&'static str>,
+    limit: Option<Grouped<u128>>,
+}
+
+#[test]
+fn number_styles() -> serde_kdl::Result {
+    let styles = Styles {
+        mode: Octal(0o644),
+        mask: Hex(-0x7F),
+        bits: vec![Binary(0b1010), Binary(0)],
+        total: Grouped(-1234567),
+        name: Hex("not a number"),
+        limit: Some(Grouped(u128::MAX)),
+    };
+    insta::assert_snapshot!("human number styles", to_string(&styles)?);
+    insta::assert_snapshot!("ugly number styles", to_string_ugly(&styles)?);
+    Ok(())
+}
+
+/// A value which contains itself, without end.
+struct Forever;
+
+impl Serialize for Forever {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct("Forever", self)
+    }
+}
+
+#[test]
+fn max_depth() -> serde_kdl::Result {
+    let err = to_string(&Forever).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            serde_kdl::Error::Limit {
+                limit: Limit::Depth,
+                max: 128,
+                location: None,
+            }
+        ),
+        "{}",
+        err
+    );
+
+    let nested = vec![vec![Some(1)]];
+    let opt = Options::new().max_depth(3);
+    insta::assert_snapshot!(
+        "human max depth",
+        to_string_with_options(&nested, opt.clone())?
+    );
+    let opt = opt.max_depth(2);
+    assert!(to_string_ugly_with_options(&nested, opt).is_err());
+    Ok(())
+}